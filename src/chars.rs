@@ -0,0 +1,335 @@
+use std::borrow::Cow;
+
+use lut::{Table, Access};
+use lookup_tables::{MediaTypeChars, Token, QText, QTextWs, Ws};
+
+/// slices shorter than this are always scanned byte by byte
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+const SIMD_MIN_LEN: usize = 256;
+
+/// classes consisting of more byte ranges than this are always scanned byte by byte
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+const SIMD_MAX_RANGES: usize = 16;
+
+/// true if the byte is a (mime) token char
+#[inline]
+pub fn is_token_char(byte: u8) -> bool {
+    MediaTypeChars::check_at(byte as usize, Token)
+}
+
+/// true if the byte is qtext, i.e. can appear in a quoted string without being escaped
+#[inline]
+pub fn is_qtext_char(byte: u8) -> bool {
+    MediaTypeChars::check_at(byte as usize, QText)
+}
+
+/// true if the byte is qtext or ws
+#[inline]
+pub fn is_qtext_ws_char(byte: u8) -> bool {
+    MediaTypeChars::check_at(byte as usize, QTextWs)
+}
+
+/// true if the byte is ws (i.e. `' '` or `'\t'`)
+#[inline]
+pub fn is_ws_char(byte: u8) -> bool {
+    MediaTypeChars::check_at(byte as usize, Ws)
+}
+
+/// compares two tokens (e.g. type, subtype or parameter names) case insensitive
+///
+/// Only us-ascii letters are folded, non us-ascii chars (which can appear e.g.
+/// in values in the internationalized grammar) have to match exactly.
+#[inline]
+pub fn token_eq_ignore_ascii_case(left: &str, right: &str) -> bool {
+    left.eq_ignore_ascii_case(right)
+}
+
+/// returns the token in lower case, only allocating if it contains upper case letters
+///
+/// Like `token_eq_ignore_ascii_case` only us-ascii letters are lower cased.
+pub fn normalize_token<'a>(token: &'a str) -> Cow<'a, str> {
+    if token.bytes().any(|bch| bch.is_ascii_uppercase()) {
+        Cow::Owned(token.to_ascii_lowercase())
+    } else {
+        Cow::Borrowed(token)
+    }
+}
+
+/// true if all bytes are in the given class of the `MediaTypeChars` table
+///
+/// An empty slice is trivially all in the class.
+#[inline]
+pub fn all_in_class<A: Access<MediaTypeChars>>(bytes: &[u8], class: A) -> bool {
+    first_not_in_class(bytes, class).is_none()
+}
+
+/// returns the index of the first byte which is not in the given class
+///
+/// Returns `None` if all bytes are in the class.
+///
+/// With the `simd` feature long slices are scanned 16 bytes at a time (on x86_64)
+/// if the bytes of the class can be described by at most 16 ranges, which is the
+/// case for all single classes of `MediaTypeChars` (e.g. `Token` consists of 7 ranges,
+/// `HttpAttrChar` of 10). Other classes fall back to the byte by byte scan.
+#[inline]
+pub fn first_not_in_class<A: Access<MediaTypeChars>>(bytes: &[u8], class: A) -> Option<usize> {
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    {
+        if bytes.len() >= SIMD_MIN_LEN {
+            if let Some(ranges) = class_ranges(&class) {
+                return simd::first_not_in_ranges(bytes, &ranges, &class);
+            }
+        }
+    }
+    scalar_first_not_in_class(bytes, &class)
+}
+
+fn scalar_first_not_in_class<A: Access<MediaTypeChars>>(bytes: &[u8], class: &A) -> Option<usize> {
+    bytes.iter()
+        .position(|bch| !class.check(MediaTypeChars::lookup(*bch as usize)))
+}
+
+/// the bytes of the class as inclusive ranges, `None` if there are more than `SIMD_MAX_RANGES`
+///
+/// Classes are only known through `Access`, so this has to probe all
+/// bytes, which is why it's only used for long slices.
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+fn class_ranges<A: Access<MediaTypeChars>>(class: &A) -> Option<Vec<(u8, u8)>> {
+    let mut ranges: Vec<(u8, u8)> = Vec::with_capacity(SIMD_MAX_RANGES);
+    for bch in 0..=255u8 {
+        if !class.check(MediaTypeChars::lookup(bch as usize)) {
+            continue;
+        }
+        if let Some(last) = ranges.last_mut() {
+            if last.1 as usize + 1 == bch as usize {
+                last.1 = bch;
+                continue;
+            }
+        }
+        if ranges.len() == SIMD_MAX_RANGES {
+            return None;
+        }
+        ranges.push((bch, bch));
+    }
+    Some(ranges)
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+mod simd {
+    use std::arch::x86_64::{
+        __m128i,
+        _mm_loadu_si128, _mm_set1_epi8, _mm_setzero_si128,
+        _mm_cmpgt_epi8, _mm_cmplt_epi8,
+        _mm_andnot_si128, _mm_or_si128, _mm_xor_si128,
+        _mm_movemask_epi8
+    };
+    use lut::Access;
+    use lookup_tables::MediaTypeChars;
+
+    /// like `first_not_in_class` but classifies 16 bytes at a time
+    ///
+    /// `ranges` have to be the bytes of `class` (see `class_ranges`), the
+    /// class is only used for the tail shorter than 16 bytes.
+    pub fn first_not_in_ranges<A>(bytes: &[u8], ranges: &[(u8, u8)], class: &A) -> Option<usize>
+        where A: Access<MediaTypeChars>
+    {
+        let mut offset = 0;
+        while offset + 16 <= bytes.len() {
+            // sse2 is always available on x86_64
+            let mask = unsafe { in_ranges_mask(&bytes[offset..offset+16], ranges) };
+            if mask != 0xFFFF {
+                return Some(offset + (!mask).trailing_zeros() as usize);
+            }
+            offset += 16;
+        }
+        super::scalar_first_not_in_class(&bytes[offset..], class)
+            .map(|idx| offset + idx)
+    }
+
+    /// returns a bit mask with the bit `n` set if the byte `n` of the chunk is in any of the ranges
+    ///
+    /// # Safety
+    ///
+    /// the chunk has to be at last 16 bytes long
+    unsafe fn in_ranges_mask(chunk: &[u8], ranges: &[(u8, u8)]) -> u32 {
+        debug_assert!(chunk.len() >= 16);
+        // the comparisons are signed, flipping the highest bit maps the bytes
+        // to i8 in a order preserving way
+        let bias = _mm_set1_epi8(0x80u8 as i8);
+        let chunk = _mm_xor_si128(_mm_loadu_si128(chunk.as_ptr() as *const __m128i), bias);
+        let mut in_ranges = _mm_setzero_si128();
+        for &(low, high) in ranges {
+            let below = _mm_cmplt_epi8(chunk, _mm_set1_epi8((low ^ 0x80) as i8));
+            let above = _mm_cmpgt_epi8(chunk, _mm_set1_epi8((high ^ 0x80) as i8));
+            let outside = _mm_or_si128(below, above);
+            // `andnot` with all bits set negates `outside`
+            in_ranges = _mm_or_si128(in_ranges, _mm_andnot_si128(outside, _mm_set1_epi8(-1)));
+        }
+        _mm_movemask_epi8(in_ranges) as u32
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    mod token_eq_ignore_ascii_case {
+        use super::super::token_eq_ignore_ascii_case;
+
+        #[test]
+        fn mixed_case() {
+            assert!(token_eq_ignore_ascii_case("Text", "text"));
+            assert!(token_eq_ignore_ascii_case("HTML", "html"));
+            assert!(!token_eq_ignore_ascii_case("html", "htm"));
+        }
+
+        #[test]
+        fn non_us_ascii_is_not_folded() {
+            assert!(token_eq_ignore_ascii_case("Ä-x", "Ä-X"));
+            assert!(!token_eq_ignore_ascii_case("Ä", "ä"));
+        }
+    }
+
+    mod normalize_token {
+        use std::borrow::Cow;
+        use super::super::normalize_token;
+
+        #[test]
+        fn lower_case_is_borrowed() {
+            match normalize_token("text") {
+                Cow::Borrowed(token) => assert_eq!(token, "text"),
+                Cow::Owned(_) => panic!("unexpected allocation")
+            }
+        }
+
+        #[test]
+        fn mixed_case_is_lower_cased() {
+            assert_eq!(normalize_token("Text/HTML"), "text/html");
+        }
+
+        #[test]
+        fn non_us_ascii_is_not_folded() {
+            assert_eq!(normalize_token("ÄbC"), "Äbc");
+            assert!(matches!(normalize_token("äbc"), Cow::Borrowed(_)));
+        }
+    }
+
+    mod all_in_class {
+        use lookup_tables::Token;
+        use super::super::all_in_class;
+
+        #[test]
+        fn all_token_slice() {
+            assert!(all_in_class(b"multipart-mixed.v1+xml", Token));
+        }
+
+        #[test]
+        fn space_in_the_middle() {
+            assert!(!all_in_class(b"text plain", Token));
+        }
+
+        #[test]
+        fn empty_slice() {
+            assert!(all_in_class(b"", Token));
+        }
+    }
+
+    mod first_not_in_class {
+        use lookup_tables::{Token, QTextWs};
+        use super::super::first_not_in_class;
+
+        #[test]
+        fn all_token_slice() {
+            assert_eq!(first_not_in_class(b"multipart-mixed.v1+xml", Token), None);
+        }
+
+        #[test]
+        fn space_in_the_middle() {
+            assert_eq!(first_not_in_class(b"text plain", Token), Some(4));
+            assert_eq!(first_not_in_class(b"text plain", QTextWs), None);
+        }
+
+        #[test]
+        fn non_us_ascii() {
+            assert_eq!(first_not_in_class("ab\u{e9}".as_bytes(), Token), Some(2));
+        }
+
+        #[test]
+        fn long_slices() {
+            let mut input = vec![b'a'; 1000];
+            assert_eq!(first_not_in_class(&input, Token), None);
+            input[997] = b'/';
+            assert_eq!(first_not_in_class(&input, Token), Some(997));
+            assert_eq!(first_not_in_class(&input, QTextWs), None);
+        }
+    }
+
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    mod simd {
+        use lut::{Access, Any};
+        use lookup_tables::{MediaTypeChars, Token, QText, Ws, HttpToken, BChars, CText};
+        use super::super::simd::first_not_in_ranges;
+        use super::super::{class_ranges, scalar_first_not_in_class, SIMD_MAX_RANGES};
+
+        fn first_not_token(input: &[u8]) -> Option<usize> {
+            first_not_in_ranges(input, &class_ranges(&Token).unwrap(), &Token)
+        }
+
+        fn check_all_bytes<A: Access<MediaTypeChars>>(class: A) {
+            let ranges = class_ranges(&class).unwrap();
+            // a filler byte in the class, so that only the probed byte can be rejected
+            let filler = ranges[0].0;
+            for bch in 0..=255u8 {
+                let mut input = [filler; 16];
+                input[5] = bch;
+                assert_eq!(
+                    first_not_in_ranges(&input, &ranges, &class),
+                    scalar_first_not_in_class(&input, &class),
+                    "byte: {:#x}", bch
+                );
+            }
+        }
+
+        #[test]
+        fn matches_token_class_for_all_bytes() {
+            check_all_bytes(Token);
+        }
+
+        #[test]
+        fn matches_other_classes_for_all_bytes() {
+            check_all_bytes(QText);
+            check_all_bytes(Ws);
+            check_all_bytes(HttpToken);
+            check_all_bytes(BChars);
+            check_all_bytes(CText);
+            check_all_bytes(Any::new(QText) | Ws);
+        }
+
+        #[test]
+        fn token_ranges() {
+            let ranges = class_ranges(&Token).unwrap();
+            assert_eq!(ranges.len(), 7);
+            assert!(ranges.len() <= SIMD_MAX_RANGES);
+            assert_eq!(ranges[0], (b'!', b'!'));
+        }
+
+        #[test]
+        fn same_result_as_scalar_for_random_input() {
+            // mostly token chars so that the first non token char is at varying positions
+            let alphabet = b"aZ9-.+!#~aaaaaaaaaaaaaaaaaaaaaaaaaaaaaa ;\x00\x7f\xff\xc3";
+            let mut state = 0x2545_f491_4f6c_dd1du64;
+            for _ in 0..2000 {
+                let mut next = || {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    state
+                };
+                let len = (next() % 300) as usize;
+                let input = (0..len)
+                    .map(|_| alphabet[(next() % alphabet.len() as u64) as usize])
+                    .collect::<Vec<_>>();
+                assert_eq!(first_not_token(&input), scalar_first_not_in_class(&input, &Token));
+            }
+        }
+    }
+}
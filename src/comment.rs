@@ -0,0 +1,166 @@
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::ops::Range;
+
+use lut::{Table, Any};
+use lookup_tables::{MediaTypeChars, CText, ObsNoWsCtl, Ws};
+use qs::spec::PartialCodePoint;
+
+use quoted_string::FWSState;
+
+/// error returned when parsing a comment fails
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum CommentError {
+    /// the input does not start with `'('`
+    DoesNotStartWithParen,
+    /// the input ended before all `'('` were closed
+    Unterminated,
+    /// the byte at the given offset can not appear in a comment
+    InvalidChar(usize)
+}
+
+impl Display for CommentError {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        use self::CommentError::*;
+        match *self {
+            DoesNotStartWithParen => fter.write_str("comment does not start with '('"),
+            Unterminated => fter.write_str("comment is not terminated"),
+            InvalidChar(offset) => write!(fter, "invalid char in comment at byte {}", offset)
+        }
+    }
+}
+
+impl Error for CommentError {}
+
+/// a parser for comments (e.g. `(a (nested) comment)`) wrt. the obs mime grammar
+///
+/// Comments can appear in the obs mime grammar wherever CFWS is allowed, e.g. around
+/// the `'/'`, `';'` and `'='` of a `Content-Type` header. They can be nested, contain
+/// quoted-pairs and FWS. As in the obs grammar quoted-pairs can contain any us-ascii
+/// char and non ws control chars are allowed in the comment text. Non us-ascii chars
+/// are allowed, too, as the input is known to be utf-8.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Default)]
+pub struct CommentParser {
+    depth: usize,
+    quoted_pair: bool,
+    fws: Option<FWSState>
+}
+
+impl CommentParser {
+
+    /// create a new CommentParser
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// parses the comment the input starts with returning it's byte span
+    ///
+    /// The span includes the outermost `'('` and `')'`, so `span.end` is the index
+    /// of the first byte after the comment. Any input after the comment is ignored.
+    ///
+    /// # Error
+    ///
+    /// - `CommentError::DoesNotStartWithParen` if the input does not start with `'('`
+    /// - `CommentError::Unterminated` if the input ends before the comment is closed
+    /// - `CommentError::InvalidChar` if a char can not appear in a comment, or a
+    ///   `'\r'` is not part of a FWS
+    pub fn parse(input: &str) -> Result<Range<usize>, CommentError> {
+        if !input.starts_with('(') {
+            return Err(CommentError::DoesNotStartWithParen);
+        }
+        let mut parser = CommentParser::new();
+        for (idx, bch) in input.bytes().enumerate() {
+            if parser.advance(bch).map_err(|_| CommentError::InvalidChar(idx))? {
+                return Ok(0..idx + 1);
+            }
+        }
+        Err(CommentError::Unterminated)
+    }
+
+    /// advances the parser returning true if the byte closed the (outermost) comment
+    fn advance(&mut self, bch: u8) -> Result<bool, ()> {
+        if self.quoted_pair {
+            self.quoted_pair = false;
+            return if bch < 0x80 { Ok(false) } else { Err(()) };
+        }
+        if let Some(state) = self.fws {
+            let (next, _) = state.advance_fold(PartialCodePoint::from_utf8_byte(bch), true)
+                .map_err(|_| ())?;
+            self.fws = next;
+            return Ok(false);
+        }
+        match bch {
+            b'(' => self.depth += 1,
+            b')' => {
+                self.depth -= 1;
+                return Ok(self.depth == 0);
+            },
+            b'\\' => self.quoted_pair = true,
+            b'\r' => self.fws = Some(FWSState::new()),
+            _ => {
+                let is_ctext = MediaTypeChars::check_at(bch as usize, Any::new(CText) | ObsNoWsCtl | Ws);
+                if !is_ctext && bch < 0x80 {
+                    return Err(());
+                }
+            }
+        }
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case)]
+
+    mod CommentParser {
+        use super::super::{CommentParser, CommentError};
+
+        #[test]
+        fn simple() {
+            assert_eq!(CommentParser::parse("(simple)"), Ok(0..8));
+        }
+
+        #[test]
+        fn nested() {
+            assert_eq!(CommentParser::parse("(nested (comment))"), Ok(0..18));
+            assert_eq!(CommentParser::parse("((a)(b))"), Ok(0..8));
+        }
+
+        #[test]
+        fn trailing_input_is_ignored() {
+            let input = "(comment) ; charset=utf-8";
+            let span = CommentParser::parse(input).unwrap();
+            assert_eq!(&input[span], "(comment)");
+        }
+
+        #[test]
+        fn quoted_pairs() {
+            assert_eq!(CommentParser::parse(r"(a \) b)"), Ok(0..8));
+            assert_eq!(CommentParser::parse(r"(a \( b)"), Ok(0..8));
+        }
+
+        #[test]
+        fn fws() {
+            assert_eq!(CommentParser::parse("(a\r\n b)"), Ok(0..7));
+            assert_eq!(CommentParser::parse("(a\r b)"), Err(CommentError::InvalidChar(3)));
+        }
+
+        #[test]
+        fn unterminated() {
+            assert_eq!(CommentParser::parse("(unterminated"), Err(CommentError::Unterminated));
+            assert_eq!(CommentParser::parse("(nested (comment)"), Err(CommentError::Unterminated));
+            assert_eq!(CommentParser::parse("(a \\)"), Err(CommentError::Unterminated));
+        }
+
+        #[test]
+        fn not_a_comment() {
+            assert_eq!(CommentParser::parse("a(b)"), Err(CommentError::DoesNotStartWithParen));
+            assert_eq!(CommentParser::parse(""), Err(CommentError::DoesNotStartWithParen));
+        }
+
+        #[test]
+        fn non_us_ascii() {
+            assert_eq!(CommentParser::parse("(ä)"), Ok(0..4));
+        }
+    }
+}
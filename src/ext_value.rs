@@ -0,0 +1,1141 @@
+use std::error::Error;
+use std::fmt::{self, Display};
+
+#[cfg(feature = "encoding_rs")]
+use encoding_rs::Encoding;
+#[cfg(feature = "encoding_rs")]
+use std::borrow::Cow;
+
+use lut::Table;
+use qs::error::CoreError;
+use lookup_tables::{MediaTypeChars, Token, AttributeChar, AlphaNum, HttpAttrChar};
+use percent_encoding::{percent_decode, percent_encode, PercentError};
+use quoted_string::{
+    unquote, classify, quote_if_needed,
+    MimeParsingUtf8, MimeQuoting, MimeTokenValidator, QuotingClassExt
+};
+
+/// the parts of a RFC 2231 extended parameter value (e.g. `us-ascii'en'a%20b`)
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct ExtValue {
+    /// the charset, `None` if it was empty
+    pub charset: Option<String>,
+    /// the language tag, `None` if it was empty
+    pub language: Option<LanguageTag>,
+    /// the percent-decoded bytes of the value (still encoded using `charset`)
+    pub value: Vec<u8>
+}
+
+/// error returned when decoding a RFC 2231 extended value fails
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum ExtValueError {
+    /// the two `'\''` separating charset, language and value are missing
+    MissingSeparator,
+    /// a char which is neither a attribute-char nor part of a `%HH` sequence was found
+    InvalidChar,
+    /// a `'%'` was not followed by two hex digits
+    InvalidPercentEncoding,
+    /// the language is not a valid language tag
+    InvalidLanguageTag,
+    /// the charset is not supported (without the `encoding_rs` feature only
+    /// `utf-8` and `us-ascii` are supported)
+    UnsupportedCharset,
+    /// the percent-decoded bytes are not valid wrt. the charset
+    InvalidEncodedValue
+}
+
+impl Display for ExtValueError {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        use self::ExtValueError::*;
+        fter.write_str(match *self {
+            MissingSeparator =>
+                "extended value did not contain charset and language separated by \"'\"",
+            InvalidChar =>
+                "extended value contained a char which is not allowed in it",
+            InvalidPercentEncoding =>
+                "extended value contained a '%' not followed by two hex digits",
+            InvalidLanguageTag =>
+                "extended value contained a invalid language tag",
+            UnsupportedCharset =>
+                "the charset of the extended value is not supported",
+            InvalidEncodedValue =>
+                "extended value is not valid wrt. it's charset"
+        })
+    }
+}
+
+impl Error for ExtValueError {}
+
+impl From<PercentError> for ExtValueError {
+    fn from(_err: PercentError) -> Self {
+        ExtValueError::InvalidPercentEncoding
+    }
+}
+
+impl ExtValue {
+
+    /// decodes the value wrt. the charset into a string
+    ///
+    /// If no charset is given `us-ascii` is assumed. Without the `encoding_rs`
+    /// feature only `utf-8` and `us-ascii` are supported, with it any charset
+    /// known to `encoding_rs` is supported.
+    ///
+    /// # Error
+    ///
+    /// - `ExtValueError::UnsupportedCharset` if the charset is not supported
+    /// - `ExtValueError::InvalidEncodedValue` if the value is not valid wrt.
+    ///   the charset
+    pub fn decode_value(&self) -> Result<String, ExtValueError> {
+        let charset = self.charset.as_deref().unwrap_or("us-ascii");
+        decode_with_charset(charset, self.value.clone())
+    }
+}
+
+/// a language tag as it can appear in a RFC 2231 extended value (e.g. `en-US`)
+///
+/// Only the basic grammar `alphanum *("-" alphanum)` is validated, i.e. the tag
+/// consists of non empty subtags of ascii letters and digits separated by `'-'`.
+/// The tag is kept in the casing it was given in.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct LanguageTag(String);
+
+impl LanguageTag {
+
+    /// creates a new language tag, validating it's grammar
+    ///
+    /// # Error
+    ///
+    /// returns `ExtValueError::InvalidLanguageTag` if the tag is empty, contains
+    /// a empty subtag (e.g. `en--US`, `-en`, `en-`) or any non alphanumeric char
+    /// except the `'-'` separating subtags
+    pub fn new(tag: &str) -> Result<Self, ExtValueError> {
+        let is_valid = tag.split('-')
+            .all(|subtag| !subtag.is_empty() && subtag.bytes().all(is_alphanum));
+
+        if is_valid {
+            Ok(LanguageTag(tag.to_owned()))
+        } else {
+            Err(ExtValueError::InvalidLanguageTag)
+        }
+    }
+
+    /// returns the tag as str
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for LanguageTag {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Display for LanguageTag {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        fter.write_str(self.as_str())
+    }
+}
+
+/// error returned when reassembling RFC 2231 parameter continuations fails
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum ContinuationError {
+    /// the section index of the parameter is not a number (or has leading zeros)
+    InvalidIndex {
+        /// the parameter name including the section
+        name: String
+    },
+    /// a section is missing
+    MissingIndex {
+        /// the base name of the parameter
+        name: String,
+        /// the index of the first missing section
+        index: usize
+    },
+    /// a section appears more than once
+    DuplicateIndex {
+        /// the base name of the parameter
+        name: String,
+        /// the index of the section appearing more than once
+        index: usize
+    },
+    /// some sections are extended values and some are not
+    MixedEncoding {
+        /// the base name of the parameter
+        name: String
+    },
+    /// a extended value was malformed
+    ExtValue(ExtValueError),
+    /// a token/quoted-string value was malformed
+    QuotedString(CoreError),
+    /// the charset of a extended value is not supported
+    UnsupportedCharset(String),
+    /// the decoded bytes are not valid wrt. the charset of the extended value
+    MalformedEncodedValue
+}
+
+impl Display for ContinuationError {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        use self::ContinuationError::*;
+        match *self {
+            InvalidIndex { ref name } =>
+                write!(fter, "invalid section index in parameter name {:?}", name),
+            MissingIndex { ref name, index } =>
+                write!(fter, "section {} of parameter {:?} is missing", index, name),
+            DuplicateIndex { ref name, index } =>
+                write!(fter, "section {} of parameter {:?} appears more than once", index, name),
+            MixedEncoding { ref name } =>
+                write!(fter, "parameter {:?} mixes extended and non extended sections", name),
+            ExtValue(ref err) => Display::fmt(err, fter),
+            QuotedString(ref err) => Display::fmt(err, fter),
+            UnsupportedCharset(ref charset) =>
+                write!(fter, "unsupported charset {:?} in extended value", charset),
+            MalformedEncodedValue =>
+                fter.write_str("extended value is not valid wrt. it's charset")
+        }
+    }
+}
+
+impl Error for ContinuationError {}
+
+impl From<ExtValueError> for ContinuationError {
+    fn from(err: ExtValueError) -> Self {
+        ContinuationError::ExtValue(err)
+    }
+}
+
+impl From<CoreError> for ContinuationError {
+    fn from(err: CoreError) -> Self {
+        ContinuationError::QuotedString(err)
+    }
+}
+
+/// decodes a RFC 2231 extended value
+///
+/// The input is expected to have the form `[charset]'[language]'<value>` where
+/// value only consists of attribute-chars and `%HH` sequences. Empty charsets and
+/// language tags are returned as `None`, non empty language tags are validated
+/// (see `LanguageTag::new`).
+///
+/// The value is percent-decoded but not further decoded wrt. the charset.
+pub fn decode_ext_value(input: &str) -> Result<ExtValue, ExtValueError> {
+    let mut parts = input.splitn(3, '\'');
+    let charset = parts.next().expect("[BUG] splitn always yields at last one item");
+    let (language, value) = match (parts.next(), parts.next()) {
+        (Some(language), Some(value)) => (language, value),
+        _ => return Err(ExtValueError::MissingSeparator)
+    };
+
+    if !charset.bytes().all(is_token_char) || !language.bytes().all(is_token_char) {
+        return Err(ExtValueError::InvalidChar);
+    }
+    validate_value(value.as_bytes(), is_attribute_char)?;
+    let language = if language.is_empty() {
+        None
+    } else {
+        Some(LanguageTag::new(language)?)
+    };
+
+    Ok(ExtValue {
+        charset: non_empty(charset),
+        language,
+        value: percent_decode(value)?
+    })
+}
+
+/// encodes a value as RFC 2231 extended value
+///
+/// The output has the form `charset'[lang]'<value>` where every byte of the value
+/// which is not a attribute-char is percent encoded (using upper case hex digits).
+///
+/// # Error
+///
+/// returns `ExtValueError::InvalidChar` if the charset or language contain
+/// non token chars or if the charset is empty
+pub fn encode_ext_value(charset: &str, lang: Option<&str>, value: &[u8])
+    -> Result<String, ExtValueError>
+{
+    let lang = lang.unwrap_or("");
+    if charset.is_empty() || !charset.bytes().all(is_token_char) || !lang.bytes().all(is_token_char) {
+        return Err(ExtValueError::InvalidChar);
+    }
+
+    let mut out = String::with_capacity(charset.len() + lang.len() + 2 + value.len());
+    out.push_str(charset);
+    out.push('\'');
+    out.push_str(lang);
+    out.push('\'');
+    percent_encode(value, &mut out);
+    Ok(out)
+}
+
+/// the charsets allowed in RFC 5987 extended values
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Rfc5987Charset {
+    /// `UTF-8`
+    Utf8,
+    /// `ISO-8859-1`
+    Iso8859_1
+}
+
+/// a decoded RFC 5987 extended value (e.g. `UTF-8'en'%C2%A3%20rates`)
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Rfc5987Value {
+    /// the charset the value was encoded with
+    pub charset: Rfc5987Charset,
+    /// the language tag, `None` if it was empty
+    pub language: Option<LanguageTag>,
+    /// the decoded value
+    pub value: String
+}
+
+/// the error returned by `decode_rfc5987`
+///
+/// `ExtValueError::UnsupportedCharset` is returned for any charset but
+/// `UTF-8` and `ISO-8859-1`.
+pub type Rfc5987Error = ExtValueError;
+
+/// decodes a RFC 5987 extended value as used in HTTP headers (e.g. `Content-Disposition`)
+///
+/// Unlike RFC 2231 extended values the charset has to be `UTF-8` or `ISO-8859-1`
+/// (matched case insensitive) and only the `attr-char`s of RFC 5987 can appear
+/// without being percent encoded (e.g. `'{'` has to be encoded). The language
+/// tag is validated (see `LanguageTag::new`), a empty value is valid.
+pub fn decode_rfc5987(input: &str) -> Result<Rfc5987Value, Rfc5987Error> {
+    let mut parts = input.splitn(3, '\'');
+    let charset = parts.next().expect("[BUG] splitn always yields at last one item");
+    let (language, value) = match (parts.next(), parts.next()) {
+        (Some(language), Some(value)) => (language, value),
+        _ => return Err(ExtValueError::MissingSeparator)
+    };
+
+    let charset = if charset.eq_ignore_ascii_case("utf-8") {
+        Rfc5987Charset::Utf8
+    } else if charset.eq_ignore_ascii_case("iso-8859-1") {
+        Rfc5987Charset::Iso8859_1
+    } else {
+        return Err(ExtValueError::UnsupportedCharset);
+    };
+    validate_value(value.as_bytes(), is_http_attr_char)?;
+    let language = if language.is_empty() {
+        None
+    } else {
+        Some(LanguageTag::new(language)?)
+    };
+
+    let bytes = percent_decode(value)?;
+    let value = match charset {
+        Rfc5987Charset::Utf8 =>
+            String::from_utf8(bytes).map_err(|_| ExtValueError::InvalidEncodedValue)?,
+        // ISO-8859-1 maps each byte to the code point with the same value
+        Rfc5987Charset::Iso8859_1 => bytes.into_iter().map(char::from).collect()
+    };
+
+    Ok(Rfc5987Value { charset, language, value })
+}
+
+/// a decoded parameter value (see `parse_param_value`)
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct ParamValue {
+    /// the decoded value
+    pub value: String,
+    /// the charset of a extended value, `None` for other values or if it was empty
+    pub charset: Option<String>,
+    /// the language tag of a extended value, `None` for other values or if it was empty
+    pub language: Option<LanguageTag>
+}
+
+/// error returned by `parse_param_value`
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum ParamError {
+    /// the parameter name ends with `'*'` but the value is not a valid extended value
+    ExtValue(ExtValueError),
+    /// the value is neither a valid token nor a valid quoted-string
+    QuotedString(CoreError)
+}
+
+impl Display for ParamError {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParamError::ExtValue(ref err) => Display::fmt(err, fter),
+            ParamError::QuotedString(ref err) => Display::fmt(err, fter)
+        }
+    }
+}
+
+impl Error for ParamError {}
+
+impl From<ExtValueError> for ParamError {
+    fn from(err: ExtValueError) -> Self {
+        ParamError::ExtValue(err)
+    }
+}
+
+impl From<CoreError> for ParamError {
+    fn from(err: CoreError) -> Self {
+        ParamError::QuotedString(err)
+    }
+}
+
+/// decodes a parameter value, detecting the form it is encoded in
+///
+/// - if the name ends with `'*'` the value is decoded as extended value
+///   (see `decode_ext_value` and `ExtValue::decode_value`)
+/// - else if the value starts with `'"'` it is unquoted
+/// - else it is treated as token
+///
+/// Quoted-strings use the internationalized, modern mime grammar. Continuations
+/// (e.g. `name*0`) are not handled, see `reassemble_continuations` for them.
+pub fn parse_param_value(name: &str, raw: &str) -> Result<ParamValue, ParamError> {
+    if name.ends_with('*') {
+        let ext = decode_ext_value(raw)?;
+        Ok(ParamValue {
+            value: ext.decode_value()?,
+            charset: ext.charset,
+            language: ext.language
+        })
+    } else {
+        Ok(ParamValue {
+            value: unquote::<MimeParsingUtf8>(raw)?,
+            charset: None,
+            language: None
+        })
+    }
+}
+
+/// returns true if the value can not be represented as (quoted) parameter value
+///
+/// This is the case if any char is classified as `QuotingClass::Invalid` by the
+/// modern, non-internationalized mime grammar (`MimeQuoting`), i.e. if it contains
+/// non us-ascii chars or CTLs (except `'\t'`). Such values have to be written as
+/// extended value (e.g. `name*=utf-8''...`) using `encode_ext_value`.
+pub fn needs_ext_encoding(value: &str) -> bool {
+    classify::<MimeQuoting>(value).is_invalid()
+}
+
+/// encodes a parameter as `name=value` using the most compatible representation
+///
+/// - if the value is a token it's written as is (`name=value`)
+/// - if it can be represented as quoted-string it's quoted (`name="a value"`)
+/// - else wise (see `needs_ext_encoding`) it's written as utf-8 extended value
+///   (`name*=utf-8''a%C3%A4`)
+///
+/// The name is written as is, i.e. it is expected to be a valid token.
+pub fn encode_parameter(name: &str, value: &str) -> String {
+    let mut out = String::with_capacity(name.len() + value.len() + 3);
+    out.push_str(name);
+    if needs_ext_encoding(value) {
+        out.push_str("*=utf-8''");
+        percent_encode(value.as_bytes(), &mut out);
+    } else {
+        out.push('=');
+        //can not fail as needs_ext_encoding made sure there are no invalid chars
+        let value = quote_if_needed::<MimeQuoting, MimeTokenValidator>(value)
+            .expect("[BUG] value has no chars invalid in quoted-strings");
+        out.push_str(&value);
+    }
+    out
+}
+
+/// reassembles RFC 2231 parameter continuations (e.g. `name*0`, `name*1`, ...)
+///
+/// The input are the parameter names and their raw values (i.e. tokens, quoted-strings
+/// or extended values). Sections are grouped by their (case insensitive) base name and
+/// concatenated in the order of their index. If the first section is marked as extended
+/// value (`name*0*`) all sections have to be extended values and the result is decoded,
+/// else wise non of them may be an extended value.
+///
+/// Parameters which are not split into sections are returned unchanged, except that
+/// their value is unquoted/decoded. Quoted-strings are unquoted using `MimeParsingUtf8`. The output keeps the order in which each parameter
+/// (or its first section) appeared.
+///
+/// Extended values are decoded like with `ExtValue::decode_value`, i.e. charsets other
+/// than `utf-8` and `us-ascii` require the `encoding_rs` feature.
+pub fn reassemble_continuations(parts: &[(&str, &str)])
+    -> Result<Vec<(String, String)>, ContinuationError>
+{
+    let mut out = Vec::with_capacity(parts.len());
+    // (base name, index in out, sections)
+    let mut groups: Vec<(&str, usize, Vec<Section>)> = Vec::new();
+
+    for &(name, value) in parts {
+        match parse_param_name(name)? {
+            ParamName::Plain => {
+                out.push((name.to_owned(), unquote::<MimeParsingUtf8>(value)?));
+            },
+            ParamName::Extended(base) => {
+                let ext_value = decode_ext_value(value)?;
+                let value = decode_charset(ext_value.charset.as_deref(), ext_value.value)?;
+                out.push((base.to_owned(), value));
+            },
+            ParamName::Section { base, index, extended } => {
+                let section = Section { index, extended, value };
+                if let Some(group) = groups.iter_mut().find(|g| g.0.eq_ignore_ascii_case(base)) {
+                    group.2.push(section);
+                    continue;
+                }
+                groups.push((base, out.len(), vec![section]));
+                // placeholder replaced once all sections are known
+                out.push((base.to_owned(), String::new()));
+            }
+        }
+    }
+
+    for (base, out_idx, sections) in groups {
+        out[out_idx].1 = assemble_sections(base, sections)?;
+    }
+    Ok(out)
+}
+
+struct Section<'a> {
+    index: usize,
+    extended: bool,
+    value: &'a str
+}
+
+enum ParamName<'a> {
+    Plain,
+    Extended(&'a str),
+    Section { base: &'a str, index: usize, extended: bool }
+}
+
+fn parse_param_name<'a>(name: &'a str) -> Result<ParamName<'a>, ContinuationError> {
+    let star_idx = match name.find('*') {
+        Some(idx) => idx,
+        None => return Ok(ParamName::Plain)
+    };
+    let base = &name[..star_idx];
+    let section = &name[star_idx+1..];
+    if section.is_empty() {
+        return Ok(ParamName::Extended(base));
+    }
+
+    let (index, extended) = match section.strip_suffix('*') {
+        Some(index) => (index, true),
+        None => (section, false)
+    };
+
+    let is_valid_index = !index.is_empty()
+        && index.bytes().all(|bch| bch.is_ascii_digit())
+        && (index == "0" || !index.starts_with('0'));
+
+    match index.parse() {
+        Ok(index) if is_valid_index => Ok(ParamName::Section { base, index, extended }),
+        _ => Err(ContinuationError::InvalidIndex { name: name.to_owned() })
+    }
+}
+
+fn assemble_sections(base: &str, mut sections: Vec<Section>) -> Result<String, ContinuationError> {
+    sections.sort_by_key(|section| section.index);
+
+    for (expected, section) in sections.iter().enumerate() {
+        if section.index < expected {
+            return Err(ContinuationError::DuplicateIndex { name: base.to_owned(), index: section.index });
+        }
+        if section.index > expected {
+            return Err(ContinuationError::MissingIndex { name: base.to_owned(), index: expected });
+        }
+    }
+
+    let extended = sections[0].extended;
+    if sections.iter().any(|section| section.extended != extended) {
+        return Err(ContinuationError::MixedEncoding { name: base.to_owned() });
+    }
+
+    if extended {
+        let first = decode_ext_value(sections[0].value)?;
+        let mut bytes = first.value;
+        for section in &sections[1..] {
+            validate_value(section.value.as_bytes(), is_attribute_char)?;
+            bytes.extend(percent_decode(section.value).map_err(ExtValueError::from)?);
+        }
+        decode_charset(first.charset.as_deref(), bytes)
+    } else {
+        let mut out = String::new();
+        for section in &sections {
+            out.push_str(&unquote::<MimeParsingUtf8>(section.value)?);
+        }
+        Ok(out)
+    }
+}
+
+/// error returned by `transcode`
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum TranscodeError {
+    /// the charset label is unknown (or not supported without the `encoding_rs` feature)
+    UnknownCharset(String),
+    /// the bytes are not valid wrt. the charset
+    MalformedEncodedValue
+}
+
+impl Display for TranscodeError {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TranscodeError::UnknownCharset(ref label) =>
+                write!(fter, "unknown or unsupported charset {:?}", label),
+            TranscodeError::MalformedEncodedValue =>
+                fter.write_str("value is not valid wrt. it's charset")
+        }
+    }
+}
+
+impl Error for TranscodeError {}
+
+/// decodes the bytes wrt. the charset with the given label into a string
+///
+/// `utf-8` and `us-ascii` are always supported, with the `encoding_rs` feature any
+/// label known to `encoding_rs::Encoding::for_label` is supported, too.
+/// Labels are matched case insensitive.
+///
+/// # Error
+///
+/// - `TranscodeError::UnknownCharset` if the label is not known/supported
+/// - `TranscodeError::MalformedEncodedValue` if the bytes are not valid wrt. the charset
+pub fn transcode(label: &str, bytes: &[u8]) -> Result<String, TranscodeError> {
+    decode_with_charset(label, bytes.to_vec()).map_err(|err| match err {
+        ExtValueError::UnsupportedCharset => TranscodeError::UnknownCharset(label.to_owned()),
+        _ => TranscodeError::MalformedEncodedValue
+    })
+}
+
+fn decode_charset(charset: Option<&str>, bytes: Vec<u8>) -> Result<String, ContinuationError> {
+    let charset = charset.unwrap_or("us-ascii");
+    decode_with_charset(charset, bytes).map_err(|err| match err {
+        ExtValueError::UnsupportedCharset =>
+            ContinuationError::UnsupportedCharset(charset.to_owned()),
+        _ => ContinuationError::MalformedEncodedValue
+    })
+}
+
+fn decode_with_charset(charset: &str, bytes: Vec<u8>) -> Result<String, ExtValueError> {
+    if charset.eq_ignore_ascii_case("us-ascii") {
+        if !bytes.is_ascii() {
+            return Err(ExtValueError::InvalidEncodedValue);
+        }
+    } else if !charset.eq_ignore_ascii_case("utf-8") {
+        return decode_other_charset(charset, &bytes);
+    }
+    String::from_utf8(bytes).map_err(|_| ExtValueError::InvalidEncodedValue)
+}
+
+#[cfg(feature = "encoding_rs")]
+fn decode_other_charset(charset: &str, bytes: &[u8]) -> Result<String, ExtValueError> {
+    let encoding = Encoding::for_label(charset.as_bytes())
+        .ok_or(ExtValueError::UnsupportedCharset)?;
+    encoding.decode_without_bom_handling_and_without_replacement(bytes)
+        .map(Cow::into_owned)
+        .ok_or(ExtValueError::InvalidEncodedValue)
+}
+
+#[cfg(not(feature = "encoding_rs"))]
+fn decode_other_charset(_charset: &str, _bytes: &[u8]) -> Result<String, ExtValueError> {
+    Err(ExtValueError::UnsupportedCharset)
+}
+
+fn validate_value(value: &[u8], is_value_char: fn(u8) -> bool) -> Result<(), ExtValueError> {
+    let mut idx = 0;
+    while idx < value.len() {
+        let bch = value[idx];
+        if bch == b'%' {
+            let is_hh = value.get(idx+1..idx+3)
+                .map(|hh| hh.iter().all(u8::is_ascii_hexdigit))
+                .unwrap_or(false);
+            if !is_hh {
+                return Err(ExtValueError::InvalidPercentEncoding);
+            }
+            idx += 3;
+        } else if is_value_char(bch) {
+            idx += 1;
+        } else {
+            return Err(ExtValueError::InvalidChar);
+        }
+    }
+    Ok(())
+}
+
+fn is_token_char(bch: u8) -> bool {
+    MediaTypeChars::check_at(bch as usize, Token)
+}
+
+fn is_attribute_char(bch: u8) -> bool {
+    MediaTypeChars::check_at(bch as usize, AttributeChar)
+}
+
+fn is_http_attr_char(bch: u8) -> bool {
+    MediaTypeChars::check_at(bch as usize, HttpAttrChar)
+}
+
+fn is_alphanum(bch: u8) -> bool {
+    MediaTypeChars::check_at(bch as usize, AlphaNum)
+}
+
+fn non_empty(part: &str) -> Option<String> {
+    if part.is_empty() {
+        None
+    } else {
+        Some(part.to_owned())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case)]
+
+    mod decode_ext_value {
+        use super::super::{decode_ext_value, ExtValue, ExtValueError, LanguageTag};
+
+        #[test]
+        fn full_ext_value() {
+            let res = decode_ext_value("us-ascii'en-us'This%20is%20%2A%2A%2Afun%2A%2A%2A");
+            assert_eq!(res, Ok(ExtValue {
+                charset: Some("us-ascii".to_owned()),
+                language: Some(LanguageTag::new("en-us").unwrap()),
+                value: b"This is ***fun***".to_vec()
+            }));
+        }
+
+        #[test]
+        fn non_us_ascii_bytes() {
+            let res = decode_ext_value("iso-8859-1'en'%A9%202000").unwrap();
+            assert_eq!(res.value, b"\xA9 2000".to_vec());
+        }
+
+        #[test]
+        fn empty_charset() {
+            let res = decode_ext_value("'en'abc").unwrap();
+            assert_eq!(res.charset, None);
+            assert_eq!(res.language.as_ref().map(LanguageTag::as_str), Some("en"));
+        }
+
+        #[test]
+        fn empty_language() {
+            let res = decode_ext_value("utf-8''abc").unwrap();
+            assert_eq!(res.charset, Some("utf-8".to_owned()));
+            assert_eq!(res.language, None);
+            assert_eq!(res.value, b"abc".to_vec());
+        }
+
+        #[test]
+        fn empty_value() {
+            let res = decode_ext_value("utf-8''").unwrap();
+            assert_eq!(res.value, Vec::<u8>::new());
+        }
+
+        #[test]
+        fn missing_separator() {
+            assert_eq!(decode_ext_value("utf-8'abc"), Err(ExtValueError::MissingSeparator));
+            assert_eq!(decode_ext_value("abc"), Err(ExtValueError::MissingSeparator));
+        }
+
+        #[test]
+        fn stray_percent() {
+            assert_eq!(decode_ext_value("utf-8''abc%"), Err(ExtValueError::InvalidPercentEncoding));
+            assert_eq!(decode_ext_value("utf-8''abc%2"), Err(ExtValueError::InvalidPercentEncoding));
+            assert_eq!(decode_ext_value("utf-8''%G0"), Err(ExtValueError::InvalidPercentEncoding));
+        }
+
+        #[test]
+        fn non_attribute_chars_are_rejected() {
+            assert_eq!(decode_ext_value("utf-8''a b"), Err(ExtValueError::InvalidChar));
+            assert_eq!(decode_ext_value("utf-8''a*b"), Err(ExtValueError::InvalidChar));
+            assert_eq!(decode_ext_value("utf-8''a'b"), Err(ExtValueError::InvalidChar));
+            assert_eq!(decode_ext_value("utf 8''ab"), Err(ExtValueError::InvalidChar));
+        }
+
+        #[test]
+        fn invalid_language_tag_is_rejected() {
+            let res = decode_ext_value("utf-8'en--US'abc");
+            assert_eq!(res, Err(ExtValueError::InvalidLanguageTag));
+        }
+    }
+
+    mod ExtValue {
+        use super::super::{decode_ext_value, ExtValueError};
+
+        mod decode_value {
+            use super::*;
+
+            #[test]
+            fn utf8_pound_sign() {
+                let ext_value = decode_ext_value("utf-8'en'%C2%A3").unwrap();
+                assert_eq!(ext_value.language.as_ref().map(|tag| tag.as_str()), Some("en"));
+                assert_eq!(ext_value.decode_value().unwrap(), "£");
+            }
+
+            #[test]
+            fn us_ascii() {
+                let ext_value = decode_ext_value("US-ASCII''a%20b").unwrap();
+                assert_eq!(ext_value.decode_value().unwrap(), "a b");
+                let ext_value = decode_ext_value("''a%20b").unwrap();
+                assert_eq!(ext_value.decode_value().unwrap(), "a b");
+            }
+
+            #[test]
+            fn invalid_percent_escape() {
+                assert_eq!(decode_ext_value("utf-8''%C2%A"), Err(ExtValueError::InvalidPercentEncoding));
+            }
+
+            #[test]
+            fn invalid_encoded_value() {
+                let ext_value = decode_ext_value("utf-8''%C2").unwrap();
+                assert_eq!(ext_value.decode_value(), Err(ExtValueError::InvalidEncodedValue));
+                let ext_value = decode_ext_value("us-ascii''%C2%A3").unwrap();
+                assert_eq!(ext_value.decode_value(), Err(ExtValueError::InvalidEncodedValue));
+            }
+
+            #[cfg(not(feature = "encoding_rs"))]
+            #[test]
+            fn other_charsets_need_encoding_rs() {
+                let ext_value = decode_ext_value("iso-8859-1''%A3").unwrap();
+                assert_eq!(ext_value.decode_value(), Err(ExtValueError::UnsupportedCharset));
+            }
+
+            #[cfg(feature = "encoding_rs")]
+            #[test]
+            fn other_charsets_with_encoding_rs() {
+                let ext_value = decode_ext_value("iso-8859-1''%A3").unwrap();
+                assert_eq!(ext_value.decode_value().unwrap(), "£");
+                let ext_value = decode_ext_value("x-unknown''%A3").unwrap();
+                assert_eq!(ext_value.decode_value(), Err(ExtValueError::UnsupportedCharset));
+            }
+        }
+    }
+
+    mod transcode {
+        use media_type::ParseError;
+        use super::super::{transcode, TranscodeError};
+
+        #[test]
+        fn native_charsets() {
+            assert_eq!(transcode("utf-8", b"\xC2\xA3").unwrap(), "£");
+            assert_eq!(transcode("UTF-8", b"abc").unwrap(), "abc");
+            assert_eq!(transcode("us-ascii", b"abc").unwrap(), "abc");
+        }
+
+        #[test]
+        fn malformed_bytes() {
+            assert_eq!(transcode("utf-8", b"\xC2"), Err(TranscodeError::MalformedEncodedValue));
+            assert_eq!(transcode("us-ascii", b"\xA3"), Err(TranscodeError::MalformedEncodedValue));
+        }
+
+        #[test]
+        fn unknown_label() {
+            let res = transcode("x-no-such-charset", b"abc");
+            assert_eq!(res, Err(TranscodeError::UnknownCharset("x-no-such-charset".to_owned())));
+        }
+
+        #[test]
+        fn converts_into_parse_error() {
+            let err = transcode("x-no-such-charset", b"abc").unwrap_err();
+            assert_eq!(ParseError::from(err), ParseError::UnknownCharset("x-no-such-charset".to_owned()));
+            let err = transcode("utf-8", b"\xC2").unwrap_err();
+            assert_eq!(ParseError::from(err), ParseError::MalformedEncodedValue);
+        }
+
+        #[cfg(not(feature = "encoding_rs"))]
+        #[test]
+        fn other_labels_need_encoding_rs() {
+            let res = transcode("iso-8859-1", b"\xA3");
+            assert_eq!(res, Err(TranscodeError::UnknownCharset("iso-8859-1".to_owned())));
+        }
+
+        #[cfg(feature = "encoding_rs")]
+        #[test]
+        fn other_labels_with_encoding_rs() {
+            assert_eq!(transcode("iso-8859-1", b"\xA3").unwrap(), "£");
+            assert_eq!(transcode("shift_jis", b"\x82\xA0").unwrap(), "あ");
+            assert_eq!(transcode("shift_jis", b"\x82"), Err(TranscodeError::MalformedEncodedValue));
+        }
+    }
+
+    mod encode_ext_value {
+        use super::super::{encode_ext_value, decode_ext_value, ExtValueError, LanguageTag};
+
+        #[test]
+        fn encode_with_language() {
+            let res = encode_ext_value("utf-8", Some("en"), "© 2000".as_bytes());
+            assert_eq!(res.unwrap(), "utf-8'en'%C2%A9%202000");
+        }
+
+        #[test]
+        fn encode_without_language() {
+            let res = encode_ext_value("us-ascii", None, b"a*b'c%d");
+            assert_eq!(res.unwrap(), "us-ascii''a%2Ab%27c%25d");
+        }
+
+        #[test]
+        fn reject_invalid_charset() {
+            let res = encode_ext_value("utf 8", None, b"abc");
+            assert_eq!(res, Err(ExtValueError::InvalidChar));
+            let res = encode_ext_value("", None, b"abc");
+            assert_eq!(res, Err(ExtValueError::InvalidChar));
+        }
+
+        #[test]
+        fn round_trip() {
+            let value = "© 2000".as_bytes();
+            let encoded = encode_ext_value("utf-8", Some("en"), value).unwrap();
+            let decoded = decode_ext_value(&encoded).unwrap();
+            assert_eq!(decoded.charset, Some("utf-8".to_owned()));
+            assert_eq!(decoded.language.as_ref().map(LanguageTag::as_str), Some("en"));
+            assert_eq!(decoded.value, value.to_vec());
+        }
+    }
+
+    mod decode_rfc5987 {
+        use super::super::{decode_rfc5987, Rfc5987Charset, ExtValueError, LanguageTag};
+
+        #[test]
+        fn utf8_filename_with_accents() {
+            let res = decode_rfc5987("UTF-8'fr'r%C3%A9sum%C3%A9.pdf").unwrap();
+            assert_eq!(res.charset, Rfc5987Charset::Utf8);
+            assert_eq!(res.language.as_ref().map(LanguageTag::as_str), Some("fr"));
+            assert_eq!(res.value, "résumé.pdf");
+        }
+
+        #[test]
+        fn iso_8859_1() {
+            let res = decode_rfc5987("iso-8859-1''%A3%20rates").unwrap();
+            assert_eq!(res.charset, Rfc5987Charset::Iso8859_1);
+            assert_eq!(res.language, None);
+            assert_eq!(res.value, "£ rates");
+        }
+
+        #[test]
+        fn empty_value() {
+            let res = decode_rfc5987("utf-8'en'").unwrap();
+            assert_eq!(res.value, "");
+        }
+
+        #[test]
+        fn unsupported_charset() {
+            assert_eq!(decode_rfc5987("us-ascii''abc"), Err(ExtValueError::UnsupportedCharset));
+            assert_eq!(decode_rfc5987("''abc"), Err(ExtValueError::UnsupportedCharset));
+        }
+
+        #[test]
+        fn stricter_attr_chars() {
+            assert_eq!(decode_rfc5987("utf-8''a{b}"), Err(ExtValueError::InvalidChar));
+            assert_eq!(decode_rfc5987("utf-8''a%7Bb%7D").unwrap().value, "a{b}");
+            assert_eq!(decode_rfc5987("utf-8''a b"), Err(ExtValueError::InvalidChar));
+        }
+
+        #[test]
+        fn malformed_input() {
+            assert_eq!(decode_rfc5987("utf-8'abc"), Err(ExtValueError::MissingSeparator));
+            assert_eq!(decode_rfc5987("utf-8''%C3"), Err(ExtValueError::InvalidEncodedValue));
+            assert_eq!(decode_rfc5987("utf-8''%G0"), Err(ExtValueError::InvalidPercentEncoding));
+            assert_eq!(decode_rfc5987("utf-8'e n'a"), Err(ExtValueError::InvalidLanguageTag));
+        }
+    }
+
+    mod parse_param_value {
+        use qs::error::CoreError;
+        use super::super::{parse_param_value, ParamError, ExtValueError, LanguageTag};
+
+        #[test]
+        fn token() {
+            let value = parse_param_value("charset", "utf-8").unwrap();
+            assert_eq!(value.value, "utf-8");
+            assert_eq!(value.charset, None);
+            assert_eq!(value.language, None);
+        }
+
+        #[test]
+        fn quoted_string() {
+            let value = parse_param_value("title", "\"a \\\"b\\\"\"").unwrap();
+            assert_eq!(value.value, "a \"b\"");
+            assert_eq!(value.charset, None);
+        }
+
+        #[test]
+        fn extended_value() {
+            let value = parse_param_value("title*", "utf-8'en'%C2%A9%20x").unwrap();
+            assert_eq!(value.value, "© x");
+            assert_eq!(value.charset, Some("utf-8".to_owned()));
+            assert_eq!(value.language.as_ref().map(LanguageTag::as_str), Some("en"));
+        }
+
+        #[test]
+        fn star_name_with_non_extended_value() {
+            let err = parse_param_value("title*", "\"abc\"").unwrap_err();
+            assert_eq!(err, ParamError::ExtValue(ExtValueError::MissingSeparator));
+            let err = parse_param_value("title*", "abc").unwrap_err();
+            assert_eq!(err, ParamError::ExtValue(ExtValueError::MissingSeparator));
+        }
+
+        #[test]
+        fn invalid_values() {
+            let err = parse_param_value("title", "a b").unwrap_err();
+            assert_eq!(err, ParamError::QuotedString(CoreError::InvalidChar));
+            let err = parse_param_value("title*", "us-ascii''%C2%A9").unwrap_err();
+            assert_eq!(err, ParamError::ExtValue(ExtValueError::InvalidEncodedValue));
+        }
+    }
+
+    mod encode_parameter {
+        use super::super::{encode_parameter, parse_param_value};
+
+        #[test]
+        fn token_is_written_bare() {
+            assert_eq!(encode_parameter("charset", "utf-8"), "charset=utf-8");
+        }
+
+        #[test]
+        fn non_token_is_quoted() {
+            assert_eq!(encode_parameter("title", "a b"), "title=\"a b\"");
+            assert_eq!(encode_parameter("title", ""), "title=\"\"");
+            assert_eq!(encode_parameter("title", "a\"b"), "title=\"a\\\"b\"");
+        }
+
+        #[test]
+        fn non_us_ascii_uses_ext_value() {
+            assert_eq!(encode_parameter("title", "ä b"), "title*=utf-8''%C3%A4%20b");
+        }
+
+        #[test]
+        fn ext_value_round_trips() {
+            let encoded = encode_parameter("title", "£ rates");
+            let mut iter = encoded.splitn(2, '=');
+            let (name, raw) = (iter.next().unwrap(), iter.next().unwrap());
+            let param = parse_param_value(name, raw).unwrap();
+            assert_eq!(param.value, "£ rates");
+        }
+    }
+
+    mod needs_ext_encoding {
+        use super::super::needs_ext_encoding;
+
+        #[test]
+        fn plain_us_ascii() {
+            assert!(!needs_ext_encoding("abc"));
+            assert!(!needs_ext_encoding("a b\t\"c\"\\"));
+            assert!(!needs_ext_encoding(""));
+        }
+
+        #[test]
+        fn non_us_ascii() {
+            assert!(needs_ext_encoding("a\u{a0}b"));
+            assert!(needs_ext_encoding("ä"));
+        }
+
+        #[test]
+        fn ctls() {
+            assert!(needs_ext_encoding("a\r\nb"));
+            assert!(needs_ext_encoding("\0"));
+            assert!(needs_ext_encoding("\x7f"));
+        }
+    }
+
+    mod LanguageTag {
+        use super::super::{LanguageTag, ExtValueError};
+
+        #[test]
+        fn valid_tags() {
+            for tag in &["en", "en-US", "zh-Hant", "de-CH-1901"] {
+                assert_eq!(LanguageTag::new(tag).unwrap().as_str(), *tag);
+            }
+        }
+
+        #[test]
+        fn empty_subtags_are_rejected() {
+            for tag in &["en--US", "", "-en", "en-"] {
+                assert_eq!(LanguageTag::new(tag), Err(ExtValueError::InvalidLanguageTag));
+            }
+        }
+
+        #[test]
+        fn non_alphanumeric_chars_are_rejected() {
+            for tag in &["en_US", "en US", "zh-Hänt"] {
+                assert_eq!(LanguageTag::new(tag), Err(ExtValueError::InvalidLanguageTag));
+            }
+        }
+
+        #[test]
+        fn display() {
+            assert_eq!(format!("{}", LanguageTag::new("zh-Hant").unwrap()), "zh-Hant");
+        }
+    }
+
+    mod reassemble_continuations {
+        use super::super::{reassemble_continuations, ContinuationError, ExtValueError};
+
+        fn owned(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+            pairs.iter().map(|&(n, v)| (n.to_owned(), v.to_owned())).collect()
+        }
+
+        #[test]
+        fn non_continued_parameters_are_kept() {
+            let res = reassemble_continuations(&[
+                ("charset", "utf-8"),
+                ("name", "\"a b\""),
+                ("title*", "utf-8'en'%C2%A3")
+            ]);
+            assert_eq!(res.unwrap(), owned(&[("charset", "utf-8"), ("name", "a b"), ("title", "£")]));
+        }
+
+        #[test]
+        fn sections_are_concatenated_in_order() {
+            let res = reassemble_continuations(&[
+                ("charset", "utf-8"),
+                ("name*1", "\" world\""),
+                ("name*0", "hello"),
+                ("x", "y")
+            ]);
+            assert_eq!(res.unwrap(), owned(&[("charset", "utf-8"), ("name", "hello world"), ("x", "y")]));
+        }
+
+        #[test]
+        fn extended_sections_are_decoded() {
+            let res = reassemble_continuations(&[
+                ("title*0*", "us-ascii'en'This%20is%20even%20more%20"),
+                ("title*1*", "%2A%2A%2Afun%2A%2A%2A%20"),
+                ("title*2*", "isn't%20it!")
+            ]);
+            assert_eq!(res, Err(ContinuationError::ExtValue(ExtValueError::InvalidChar)));
+
+            let res = reassemble_continuations(&[
+                ("title*0*", "utf-8'en'%C2"),
+                ("title*1*", "%A3%20"),
+                ("title*2*", "abc")
+            ]);
+            assert_eq!(res.unwrap(), owned(&[("title", "£ abc")]));
+        }
+
+        #[test]
+        fn gaps_are_rejected() {
+            let res = reassemble_continuations(&[("name*0", "a"), ("name*2", "c")]);
+            assert_eq!(res, Err(ContinuationError::MissingIndex { name: "name".to_owned(), index: 1 }));
+            let res = reassemble_continuations(&[("name*1", "b")]);
+            assert_eq!(res, Err(ContinuationError::MissingIndex { name: "name".to_owned(), index: 0 }));
+        }
+
+        #[test]
+        fn duplicates_are_rejected() {
+            let res = reassemble_continuations(&[("name*0", "a"), ("NAME*1", "b"), ("name*1", "c")]);
+            assert_eq!(res, Err(ContinuationError::DuplicateIndex { name: "name".to_owned(), index: 1 }));
+        }
+
+        #[test]
+        fn mixed_encoding_is_rejected() {
+            let res = reassemble_continuations(&[("name*0*", "utf-8''a"), ("name*1", "\"b\"")]);
+            assert_eq!(res, Err(ContinuationError::MixedEncoding { name: "name".to_owned() }));
+            let res = reassemble_continuations(&[("name*0", "a"), ("name*1*", "b")]);
+            assert_eq!(res, Err(ContinuationError::MixedEncoding { name: "name".to_owned() }));
+        }
+
+        #[test]
+        fn invalid_index_is_rejected() {
+            for name in &["name*a", "name*01", "name**", "name*0**"] {
+                let res = reassemble_continuations(&[(name, "a")]);
+                assert_eq!(res, Err(ContinuationError::InvalidIndex { name: name.to_string() }));
+            }
+        }
+
+        #[test]
+        fn unsupported_charset_is_rejected() {
+            let res = reassemble_continuations(&[("name*", "x-unknown''%A3")]);
+            assert_eq!(res, Err(ContinuationError::UnsupportedCharset("x-unknown".to_owned())));
+        }
+    }
+}
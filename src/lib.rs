@@ -7,10 +7,24 @@
 //!   are provided for a number of different use cases including media-types in http, mime as well as
 //!   a more strict impl for media types compatible with all other implementations and a impl being
 //!   usable with media-types compatible with any (/at last one) of the other implementations.
+//!   With the `debug-trace` feature `trace_parse` records the automaton state after each byte.
 //!
 //! - `lookup_table`: provides a `lut` lookup table for bytes/us-ascii chars used in context of
 //!   media-type parsing.
 //!
+//! - `chars`: provides simple predicates for the most common char classes of the lookup table
+//!   for users which do not want to depend on `lut` directly. With the `simd` feature scanning
+//!   long slices for `Token` chars is done 16 bytes at a time on x86_64.
+//!
+//! - `ext_value`: provides decoding/encoding of RFC 2231 extended parameter values (e.g. `utf-8''a%20b`)
+//!   and decoding of the RFC 5987 extended values used in HTTP.
+//!   Decoding values in charsets other than `utf-8` and `us-ascii` requires the `encoding_rs` feature.
+//!
+//! - `comment`: provides a `CommentParser` for (nested) comments of the obs mime grammar.
+//!
+//! - `media_type`: provides a simple `MediaType` parser build on top of the other utils.
+//!   With the `serde` feature `MediaType` can be (de-)serialized from/to it's string form.
+//!
 //! **Note: Currently is crate is rather unstable. It will still keep to
 //!  semver but changing to a newer (braking) version might induce large
 //!  api changes. Sill bug-fixes to older versions can be done if requested
@@ -23,10 +37,31 @@
 extern crate lut;
 extern crate quoted_string as qs;
 extern crate percent_encoding as penc;
+#[cfg(feature = "encoding_rs")]
+extern crate encoding_rs;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
 
 /// lut lookup tables for parsing media types
 pub mod lookup_tables;
 /// impl of traits from the quoted-string crate for parsing media types
 pub mod quoted_string;
 /// impl of EncodingSet's for encoding parameter values if needed
-pub mod percent_encoding;
\ No newline at end of file
+pub mod percent_encoding;
+/// predicates for char classes relevant when parsing media types
+pub mod chars;
+/// decoding/encoding of RFC 2231 extended parameter values
+pub mod ext_value;
+/// a simple media type parser build on top of the other utils
+pub mod media_type;
+/// parsing of comments as they can appear in the obs mime grammar
+pub mod comment;
+
+/// names used by the exported macros, not part of the public api
+#[doc(hidden)]
+pub mod __macro_support {
+    pub use qs::spec::{State, ParsingImpl, PartialCodePoint};
+    pub use qs::error::CoreError;
+}
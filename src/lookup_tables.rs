@@ -35,14 +35,36 @@ new_table! {
         ///
         /// Note: while using a lookup just for to determine if it is `' '` or `'\t'` makes little sense
         /// using it with a already looked up value or in combination with others can make sense
-        Ws = Ws
+        Ws = Ws,
+
+        /// TSpecials characters, i.e. the characters from RFC2045 which are neither allowed
+        /// in a (mime) token nor ws or ctl (`()<>@,;:\"/[]?=`)
+        TSpecials = TS,
+
+        /// AttributeChar characters as defined in RFC2231, i.e. token chars except `'*'`,
+        /// `'\''` and `'%'` which can appear in extended values without being percent encoded
+        AttributeChar = AC,
+
+        /// AlphaNum characters, i.e. ascii letters and digits as used in the subtags of
+        /// language tags (RFC 5646)
+        AlphaNum = AN,
+
+        /// BChars characters, i.e. the characters allowed in a multipart boundary (RFC 2046),
+        /// this includes `' '` which must not be the last char of a boundary
+        BChars = BC,
+
+        /// HttpAttrChar characters, i.e. the `attr-char`s from RFC 5987 which can appear in
+        /// HTTP extended values without being percent encoded, unlike `AttributeChar` this
+        /// excludes `'{'` and `'}'`
+        HttpAttrChar = HA
     }
 
     /// MediaTypeChars is a lookup table for a number of character classes relevant when parsing media types
     ///
     /// This are mainly:
     /// `CText`, `ObsNoWsCtl`, `HttpToken`, `Token`, `RestrictedToken`,
-    /// `QText`, `DQuotesOrEscape` and `Ws`
+    /// `QText`, `DQuotesOrEscape`, `Ws`, `TSpecials`, `AttributeChar`, `AlphaNum`, `BChars`
+    /// and `HttpAttrChar`
     ///
     /// The classes `HttpToken`, `Token` and `RestrictedToken` are needed for the different specifications
     /// of a "token" in Http, Mime and for IANA registry compatible tokens.
@@ -57,47 +79,47 @@ new_table! {
     /// with lookup result already available.
     ///
     pub struct MediaTypeChars {
-        static data: [u8; 256] = [
+        static data: [u16; 256] = [
             //0x00 + 0/4/8/C
-            -,               NC,              NC,              NC,
-            NC,              NC,              NC,              NC,
-            NC,              Ws,              -,               NC,
-            NC,              -,               NC,              NC,
+            -,                           NC,                          NC,                          NC,
+            NC,                          NC,                          NC,                          NC,
+            NC,                          Ws,                          -,                           NC,
+            NC,                          -,                           NC,                          NC,
             //0x10  + 0/4/8/C
-            NC,              NC,              NC,              NC,
-            NC,              NC,              NC,              NC,
-            NC,              NC,              NC,              NC,
-            NC,              NC,              NC,              NC,
+            NC,                          NC,                          NC,                          NC,
+            NC,                          NC,                          NC,                          NC,
+            NC,                          NC,                          NC,                          NC,
+            NC,                          NC,                          NC,                          NC,
             //0x20 + 0/4/8/C
-            Ws,              CT|QC|RT|TO|HT,  CT|DOE,          CT|QC|RT|TO|HT,
-            CT|QC|RT|TO|HT,  CT|QC|TO|HT,     CT|QC|RT|TO|HT,  CT|QC|TO|HT,
-            QC,              QC,              CT|QC|TO|HT,     CT|QC|RT|TO|HT,
-            CT|QC,           CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,  CT|QC,
+            Ws|BC,                       CT|QC|RT|TO|HT|AC|HA,        CT|DOE|TS,                   CT|QC|RT|TO|HT|AC|HA,
+            CT|QC|RT|TO|HT|AC|HA,        CT|QC|TO|HT,                 CT|QC|RT|TO|HT|AC|HA,        CT|QC|TO|HT|BC,
+            QC|TS|BC,                    QC|TS|BC,                    CT|QC|TO|HT,                 CT|QC|RT|TO|HT|AC|BC|HA,
+            CT|QC|TS|BC,                 CT|QC|RT|TO|HT|AC|BC|HA,     CT|QC|RT|TO|HT|AC|BC|HA,     CT|QC|TS|BC,
             //0x30+ 0/4/8/C
-            CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,
-            CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,
-            CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,  CT|QC,           CT|QC,
-            CT|QC,           CT|QC,           CT|QC,           CT|QC,
+            CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,
+            CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,
+            CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|TS|BC,                 CT|QC|TS,
+            CT|QC|TS,                    CT|QC|TS|BC,                 CT|QC|TS,                    CT|QC|TS|BC,
             //0x40+ 0/4/8/C
-            CT|QC,           CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,
-            CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,
-            CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,
-            CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,
+            CT|QC|TS,                    CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,
+            CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,
+            CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,
+            CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,
             //0x50 + 0/4/8/C
-            CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,
-            CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,
-            CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,  CT|QC,
-            DOE,/*'\\'*/     CT|QC,           CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,
+            CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,
+            CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,
+            CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|TS,
+            DOE|TS,/*'\\'*/              CT|QC|TS,                    CT|QC|RT|TO|HT|AC|HA,        CT|QC|RT|TO|HT|AC|BC|HA,
             //0x60 + 0/4/8/C
-            CT|QC|TO|HT,     CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,
-            CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,
-            CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,
-            CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,
+            CT|QC|TO|HT|AC|HA,           CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,
+            CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,
+            CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,
+            CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,
             //0x70 + 0/4/8/C
-            CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,
-            CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,
-            CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,  CT|QC|RT|TO|HT,  CT|QC|TO,
-            CT|QC|TO|HT,     CT|QC|TO,        CT|QC|TO|HT,     NC,
+            CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,
+            CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,
+            CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|RT|TO|HT|AC|AN|BC|HA,  CT|QC|TO|AC,
+            CT|QC|TO|HT|AC|HA,           CT|QC|TO|AC,                 CT|QC|TO|HT|AC|HA,           NC,
             //0x80
             -, -, -, -, -, -, -, -, -, -, -, -, -, -, -, -,
             //0x90
@@ -133,4 +155,89 @@ accessor_any!{
 accessor_any!{
     /// VChar or Ws (i.e. `(' ' <= ch && ch <= '~') || ch == '\t'`)
     pub VCharWs = QText | DQuoteOrEscape | Ws }
+accessor_any!{
+    /// RestrictedName chars of RFC 6838 (i.e. `[A-Za-z0-9!#$&^_.+-]`)
+    ///
+    /// This is the same set as `RestrictedToken`, it is provided under the name used
+    /// by RFC 6838.
+    pub RestrictedName = RestrictedToken }
+
+#[cfg(test)]
+mod test {
+    use lut::{Table, Access};
+    use super::*;
+
+    #[test]
+    fn token_and_tspecials_are_mutually_exclusive() {
+        for x in 0usize..0x80 {
+            let lres = MediaTypeChars::lookup(x);
+            assert!(!(Token.check(lres) && TSpecials.check(lres)), "0x{:x}", x);
+        }
+    }
+
+    #[test]
+    fn printable_non_token_chars_are_tspecials() {
+        for x in b'!'..(b'~'+1) {
+            let idx = x as usize;
+            if !MediaTypeChars::check_at(idx, Token) {
+                assert!(MediaTypeChars::check_at(idx, TSpecials), "{:?}", x as char);
+            }
+        }
+    }
 
+    #[test]
+    fn tspecials_are_exactly_the_rfc2045_set() {
+        for x in 0usize..0x100 {
+            let expected = b"()<>@,;:\\\"/[]?=".contains(&(x as u8));
+            assert_eq!(MediaTypeChars::check_at(x, TSpecials), expected, "0x{:x}", x);
+        }
+    }
+
+    #[test]
+    fn restricted_name_chars() {
+        for x in 0usize..0x100 {
+            let ch = x as u8;
+            let expected = (ch < 0x7f && (ch as char).is_alphanumeric())
+                || b"!#$&^_.+-".contains(&ch);
+            assert_eq!(MediaTypeChars::check_at(x, RestrictedName), expected, "0x{:x}", x);
+        }
+        for x in b"/; \x80\xff" {
+            assert!(!MediaTypeChars::check_at(*x as usize, RestrictedName));
+        }
+    }
+
+    #[test]
+    fn attribute_chars_are_token_chars_except_some() {
+        for x in 0usize..0x100 {
+            let expected = MediaTypeChars::check_at(x, Token) && !b"*'%".contains(&(x as u8));
+            assert_eq!(MediaTypeChars::check_at(x, AttributeChar), expected, "0x{:x}", x);
+        }
+    }
+
+    #[test]
+    fn alpha_num_chars_are_ascii_letters_and_digits() {
+        for x in 0usize..0x100 {
+            let expected = (x as u8).is_ascii_alphanumeric();
+            assert_eq!(MediaTypeChars::check_at(x, AlphaNum), expected, "0x{:x}", x);
+        }
+    }
+
+    #[test]
+    fn bchars_are_alphanumeric_some_specials_and_space() {
+        for x in 0usize..0x100 {
+            let bch = x as u8;
+            let expected = bch.is_ascii_alphanumeric() || b"'()+_,-./:=? ".contains(&bch);
+            assert_eq!(MediaTypeChars::check_at(x, BChars), expected, "0x{:x}", x);
+        }
+    }
+
+    #[test]
+    fn http_attr_chars_are_http_token_chars_except_some() {
+        for x in 0usize..0x100 {
+            let expected = MediaTypeChars::check_at(x, HttpToken) && !b"*'%".contains(&(x as u8));
+            assert_eq!(MediaTypeChars::check_at(x, HttpAttrChar), expected, "0x{:x}", x);
+        }
+        assert!(MediaTypeChars::check_at(b'{' as usize, AttributeChar));
+        assert!(!MediaTypeChars::check_at(b'{' as usize, HttpAttrChar));
+    }
+}
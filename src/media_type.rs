@@ -0,0 +1,2152 @@
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::iter::FusedIterator;
+use std::str::FromStr;
+
+use qs::error::CoreError;
+use qs::spec::{
+    PartialCodePoint,
+    WithoutQuotingValidator,
+};
+
+#[cfg(feature = "serde")]
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+#[cfg(feature = "serde")]
+use serde::de::{self, Visitor};
+
+use chars::normalize_token;
+use comment::{CommentParser, CommentError};
+use ext_value::{reassemble_continuations, ContinuationError, TranscodeError};
+use quoted_string::{
+    MimeGrammar,
+    MimeTokenValidator, MimeRestrictedNameValidator, BoundaryValidator,
+    MimeParsing, MimeParsingUtf8,
+    MimeObsParsing, MimeObsParsingUtf8,
+    MimeUtf8Quoting,
+    OffsetError,
+    QuotingClassExt,
+    quoted_string_end_with_offset,
+    classify,
+    is_valid_token
+};
+
+/// error returned when parsing a media type fails
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum ParseError {
+    /// the type is empty or contains non token chars
+    InvalidType,
+    /// the `'/'` between type and subtype is missing
+    MissingSlash,
+    /// the subtype is empty or contains non token chars
+    InvalidSubtype,
+    /// something else but a `';'` followed a parameter (or the subtype)
+    ExpectedSemicolon,
+    /// the parameter name is empty or contains non token chars
+    InvalidParamName,
+    /// the parameter has no `'='` or no value
+    MissingParamValue,
+    /// the parameter value is neither a valid token nor a valid quoted-string
+    InvalidParamValue(CoreError),
+    /// the RFC 2231 continuations of a parameter could not be reassembled
+    InvalidContinuation(ContinuationError),
+    /// the charset label is unknown (or not supported without the `encoding_rs` feature)
+    UnknownCharset(String),
+    /// the bytes are not a valid wrt. the charset they are supposed to be encoded in
+    MalformedEncodedValue,
+    /// the `q` parameter of a media range is not a valid quality value (e.g. `q=1.5`)
+    InvalidQuality,
+    /// the multipart boundary contains invalid chars, ends with a space or is too long
+    InvalidBoundary,
+    /// a comment between the parameters (obs grammar only) is malformed
+    InvalidComment(CommentError),
+    /// a parameter name appears more than once (only returned by `MediaType::parse_strict(_with)`)
+    DuplicateParameter {
+        /// the name of the repeated parameter, as it appeared in the input
+        name: String
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        use self::ParseError::*;
+        match *self {
+            InvalidType => fter.write_str("type of media type is empty or not a token"),
+            MissingSlash => fter.write_str("media type has no '/' between type and subtype"),
+            InvalidSubtype => fter.write_str("subtype of media type is empty or not a token"),
+            ExpectedSemicolon => fter.write_str("expected ';' before next parameter"),
+            InvalidParamName => fter.write_str("parameter name is empty or not a token"),
+            MissingParamValue => fter.write_str("parameter has no value"),
+            InvalidParamValue(ref err) => write!(fter, "invalid parameter value: {}", err),
+            InvalidContinuation(ref err) => write!(fter, "invalid parameter continuation: {}", err),
+            UnknownCharset(ref label) => write!(fter, "unknown charset {:?}", label),
+            MalformedEncodedValue => fter.write_str("value is not valid wrt. it's charset"),
+            InvalidQuality => fter.write_str("quality value is not in the range 0.000 to 1.000"),
+            InvalidBoundary => fter.write_str("invalid multipart boundary"),
+            InvalidComment(ref err) => write!(fter, "invalid comment: {}", err),
+            DuplicateParameter { ref name } => write!(fter, "duplicate parameter {:?}", name)
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+impl From<TranscodeError> for ParseError {
+    fn from(err: TranscodeError) -> Self {
+        match err {
+            TranscodeError::UnknownCharset(label) => ParseError::UnknownCharset(label),
+            TranscodeError::MalformedEncodedValue => ParseError::MalformedEncodedValue
+        }
+    }
+}
+
+/// a `ParseError` together with the byte offset at which it occurred
+///
+/// This is returned by `MediaType::parse_with_offset`.
+pub type MimeParseError = OffsetError<ParseError>;
+
+/// the error returned by `parse_media_type`
+///
+/// Each variant of `ParseError` corresponds to the segment of the
+/// media type which failed to parse.
+pub type MediaTypeError = ParseError;
+
+/// parses a media type (e.g. `"text/plain; charset=utf-8"`)
+///
+/// This is a shortcut for `MediaType::parse`, i.e. the internationalized,
+/// modern mime grammar is used for parameter values.
+pub fn parse_media_type(input: &str) -> Result<MediaType, MediaTypeError> {
+    MediaType::parse(input)
+}
+
+/// unquotes a quoted-string parameter value using a grammar selected at runtime
+///
+/// Like `MimeGrammar::unquote` a bare token is returned as is, but a failure is
+/// reported as `ParseError::InvalidParamValue` together with the byte offset at
+/// which unquoting failed (see `unquote_with_offset`).
+pub fn parse_quoted_with(grammar: MimeGrammar, input: &str) -> Result<String, MimeParseError> {
+    grammar.unquote_with_offset(input)
+        .map_err(|err| OffsetError::new(ParseError::InvalidParamValue(err.kind), err.offset))
+}
+
+/// the registration tree of a media type subtype (RFC 6838)
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum RegistrationTree {
+    /// the standards tree, i.e. subtypes without a tree prefix (e.g. `text/plain`)
+    Standards,
+    /// the vendor tree, i.e. subtypes starting with `vnd.`
+    Vendor,
+    /// the personal/vanity tree, i.e. subtypes starting with `prs.`
+    Personal,
+    /// the unregistered tree, i.e. subtypes starting with `x.` or the legacy `x-`
+    Unregistered
+}
+
+/// a media type (e.g. `text/plain; charset="utf-8"`)
+///
+/// Type, subtype and parameter names are validated as (mime) tokens, parameter
+/// values have to be either tokens or quoted-strings wrt. the grammar used
+/// for parsing. Optional whitespace is allowed around the `';'` and `'='`
+/// separating the parameters, with the obs grammars this includes FWS and
+/// (nested) comments, which are discarded.
+///
+/// Type and subtype are normalized to lower case, parameter names are kept as
+/// they are and parameter values are stored unquoted.
+#[derive(Debug, Clone)]
+pub struct MediaType {
+    ty: String,
+    subtype: String,
+    params: Vec<(String, String)>,
+    grammar: MimeGrammar
+}
+
+impl MediaType {
+
+    /// parses a media type using the internationalized, modern mime grammar
+    pub fn parse(input: &str) -> Result<MediaType, ParseError> {
+        MediaType::parse_with(input, MimeGrammar::ModernUtf8)
+    }
+
+    /// like `parse` but rejects media types in which a parameter name appears more than once
+    ///
+    /// Parameter names are compared case insensitive. RFC 2045 does not allow
+    /// repeating parameters and implementations disagree on which occurrence
+    /// to use, so e.g. a repeated `boundary` can be used to smuggle content past
+    /// a filter.
+    ///
+    /// `parse` accepts duplicates and keeps all of them, lookups like `get_param`,
+    /// `charset` and `boundary` then use the last occurrence.
+    pub fn parse_strict(input: &str) -> Result<MediaType, ParseError> {
+        MediaType::parse_strict_with(input, MimeGrammar::ModernUtf8)
+    }
+
+    /// like `parse_strict` but uses the given grammar (see `parse_with`)
+    pub fn parse_strict_with(input: &str, grammar: MimeGrammar) -> Result<MediaType, ParseError> {
+        let media_type = MediaType::parse_with(input, grammar)?;
+        for (idx, param) in media_type.params.iter().enumerate() {
+            if media_type.params[..idx].iter().any(|other| other.0.eq_ignore_ascii_case(&param.0)) {
+                return Err(ParseError::DuplicateParameter { name: param.0.clone() });
+            }
+        }
+        Ok(media_type)
+    }
+
+    /// parses a media type using the given grammar for quoted-string parameter values
+    pub fn parse_with(input: &str, grammar: MimeGrammar) -> Result<MediaType, ParseError> {
+        MediaType::parse_with_offset(input, grammar).map_err(|err| err.kind)
+    }
+
+    /// like `parse_with` but the error also contains the byte offset at which parsing failed
+    ///
+    /// The offset points to the first byte of the offending code point, for a
+    /// unterminated quoted-string it points to the opening `'"'`.
+    pub fn parse_with_offset(input: &str, grammar: MimeGrammar)
+        -> Result<MediaType, OffsetError<ParseError>>
+    {
+        let slash_idx = token_len(input);
+        if slash_idx == 0 {
+            return Err(OffsetError::new(ParseError::InvalidType, 0));
+        }
+        match input.as_bytes().get(slash_idx) {
+            Some(&b'/') => {},
+            Some(_) => return Err(OffsetError::new(ParseError::InvalidType, slash_idx)),
+            None => return Err(OffsetError::new(ParseError::MissingSlash, slash_idx))
+        }
+
+        let subtype_len = token_len(&input[slash_idx+1..]);
+        let params_start = slash_idx + 1 + subtype_len;
+        if subtype_len == 0 {
+            return Err(OffsetError::new(ParseError::InvalidSubtype, params_start));
+        }
+        match input.as_bytes().get(params_start) {
+            None | Some(&b';') | Some(&b' ') | Some(&b'\t') => {},
+            Some(_) => return Err(OffsetError::new(ParseError::InvalidSubtype, params_start))
+        }
+        let subtype = &input[slash_idx+1..params_start];
+        if let Err(suffix_idx) = validate_suffix(subtype) {
+            return Err(OffsetError::new(ParseError::InvalidSubtype, slash_idx + 1 + suffix_idx));
+        }
+
+        let mut raw_params = RawParams::new(&input[params_start..], grammar);
+        let mut params = Vec::new();
+        while let Some(param) = raw_params.next() {
+            let (name, raw_value) = param
+                .map_err(|err| OffsetError::new(err.kind, params_start + err.offset))?;
+            let value = grammar.unquote_cow(raw_value)
+                .map_err(|err| {
+                    let value_start = params_start + raw_params.offset() - raw_value.len();
+                    OffsetError::new(ParseError::InvalidParamValue(err), value_start)
+                })?;
+            params.push((name.to_owned(), value.into_owned()));
+        }
+
+        Ok(MediaType {
+            ty: input[..slash_idx].to_ascii_lowercase(),
+            subtype: input[slash_idx+1..params_start].to_ascii_lowercase(),
+            params,
+            grammar
+        })
+    }
+
+    /// returns the (lower case) type (e.g. `"text"` for `text/plain`)
+    pub fn ty(&self) -> &str {
+        &self.ty
+    }
+
+    /// alias for `ty`
+    pub fn type_(&self) -> &str {
+        self.ty()
+    }
+
+    /// returns the (lower case) subtype (e.g. `"plain"` for `text/plain`)
+    pub fn subtype(&self) -> &str {
+        &self.subtype
+    }
+
+    /// returns the structured syntax suffix of the subtype (e.g. `"json"` for `ld+json`)
+    ///
+    /// This is the part after the last `'+'`, if there is no `'+'` `None` is
+    /// returned. When parsing it's validated that the suffix is a restricted-name,
+    /// so e.g. a trailing `'+'` is rejected.
+    pub fn suffix(&self) -> Option<&str> {
+        self.split_suffix().map(|(_, suffix)| suffix)
+    }
+
+    /// returns the subtype without the structured syntax suffix (e.g. `"ld"` for `ld+json`)
+    ///
+    /// If there is no suffix (see `suffix`) the whole subtype is returned.
+    pub fn without_suffix(&self) -> &str {
+        self.split_suffix()
+            .map(|(base, _)| base)
+            .unwrap_or(&self.subtype)
+    }
+
+    /// alias for `without_suffix`
+    ///
+    /// Provided under the "base subtype" name used for structured syntax
+    /// suffixes, it always returns the same as `without_suffix`.
+    pub fn base_subtype(&self) -> &str {
+        self.without_suffix()
+    }
+
+    fn split_suffix(&self) -> Option<(&str, &str)> {
+        let plus_idx = self.subtype.rfind('+')?;
+        let suffix = &self.subtype[plus_idx+1..];
+        if suffix.is_empty() {
+            None
+        } else {
+            Some((&self.subtype[..plus_idx], suffix))
+        }
+    }
+
+    /// returns the registration tree the subtype belongs to, based on it's prefix
+    pub fn registration_tree(&self) -> RegistrationTree {
+        let subtype = &*self.subtype;
+        if subtype.starts_with("vnd.") {
+            RegistrationTree::Vendor
+        } else if subtype.starts_with("prs.") {
+            RegistrationTree::Personal
+        } else if subtype.starts_with("x.") || subtype.starts_with("x-") {
+            RegistrationTree::Unregistered
+        } else {
+            RegistrationTree::Standards
+        }
+    }
+
+    /// true if the type is `text`
+    pub fn is_text(&self) -> bool {
+        self.ty == "text"
+    }
+
+    /// true if the type is `multipart`
+    pub fn is_multipart(&self) -> bool {
+        self.ty == "multipart"
+    }
+
+    /// true if the type is `image`
+    pub fn is_image(&self) -> bool {
+        self.ty == "image"
+    }
+
+    /// true if the body can be expected to be utf-8 (or us-ascii) text
+    ///
+    /// If there is a `charset` parameter it decides, i.e. it has to be `utf-8`
+    /// or `us-ascii`. Else wise this is true for `text/*`, `application/json`
+    /// and media types with a `+json` suffix (JSON has to be utf-8, RFC 8259).
+    pub fn is_utf8_compatible(&self) -> bool {
+        if let Some(charset) = self.charset() {
+            return charset == "utf-8" || charset == "us-ascii";
+        }
+        self.is_text()
+            || (self.ty == "application" && self.subtype == "json")
+            || self.suffix() == Some("json")
+    }
+
+    /// returns the grammar used to parse the media type
+    pub fn grammar(&self) -> MimeGrammar {
+        self.grammar
+    }
+
+    /// returns the (unquoted) value of the last parameter with the given name
+    ///
+    /// The name is compared case insensitive. If the parameter appears multiple
+    /// times the last occurrence wins (use `parse_strict` to reject duplicates).
+    pub fn get_param(&self, name: &str) -> Option<&str> {
+        self.params.iter().rev()
+            .find(|param| param.0.eq_ignore_ascii_case(name))
+            .map(|param| &*param.1)
+    }
+
+    /// returns the (unquoted) value of the `charset` parameter in lower case
+    ///
+    /// The value is only allocated if it's not already lower case. No default is
+    /// applied, i.e. `None` is returned if there is no `charset` parameter even
+    /// for `text/*` media types (for which it defaults to `us-ascii`).
+    pub fn charset<'a>(&'a self) -> Option<Cow<'a, str>> {
+        self.get_param("charset").map(normalize_token)
+    }
+
+    /// returns the (unquoted) value of the `boundary` parameter
+    ///
+    /// The value is returned as is, i.e. it's not validated (see `validate_boundary`)
+    /// and the case is kept, as boundaries are compared case sensitive.
+    pub fn boundary(&self) -> Option<&str> {
+        self.get_param("boundary")
+    }
+
+    /// returns a iterator over the parameters in the order they appear in
+    ///
+    /// The values are unquoted. The iterator borrows from the media type, it
+    /// does not allocate.
+    pub fn params<'a>(&'a self) -> impl Iterator<Item=(&'a str, &'a str)> + 'a {
+        self.params.iter().map(|param| (&*param.0, &*param.1))
+    }
+
+    /// returns a iterator over the parameter names in the order they appear in
+    pub fn param_names<'a>(&'a self) -> impl Iterator<Item=&'a str> + 'a {
+        self.params.iter().map(|param| &*param.0)
+    }
+
+    /// brings the media type into a canonical form
+    ///
+    /// This lower cases all parameter names (type and subtype already are lower
+    /// case) and sorts the parameters by name. Parameter values keep their case,
+    /// use `charset` (or `==`) to compare the `charset` case insensitive.
+    ///
+    /// If a parameter name appears multiple times only the last occurrence is
+    /// kept (consistent with `get_param`), even if the values differ.
+    pub fn normalize(&mut self) {
+        for param in self.params.iter_mut() {
+            param.0.make_ascii_lowercase();
+        }
+        // the sort is stable, so after reversing the last occurrence of a name comes first
+        self.params.reverse();
+        self.params.sort_by(|left, right| left.0.cmp(&right.0));
+        self.params.dedup_by(|later, first| later.0 == first.0);
+    }
+
+    /// like `normalize` but consumes and returns the media type
+    pub fn normalized(mut self) -> Self {
+        self.normalize();
+        self
+    }
+
+    /// true if the media type is in the given media range
+    ///
+    /// A wildcard type or subtype of the range matches anything. Every parameter of
+    /// the range has to be present on the media type with the same value, parameter
+    /// names are compared case insensitive, values case sensitive except for `charset`.
+    // `Option::is_none_or` would raise the minimal rust version to 1.82
+    #[allow(clippy::unnecessary_map_or)]
+    pub fn matches(&self, range: &MediaRange) -> bool {
+        range.ty.as_ref().map_or(true, |ty| *ty == self.ty)
+            && range.subtype.as_ref().map_or(true, |subtype| *subtype == self.subtype)
+            && self.has_all_params(&range.params)
+    }
+
+    /// like `matches` but the pattern is a media type, where a type or subtype of `*` matches anything
+    ///
+    /// Unlike with `MediaRange` the type and subtype are independent of each other,
+    /// so e.g. `*/json` matches `application/json`. Parameters of the pattern
+    /// have to be present on the media type, as with `matches`.
+    pub fn matches_pattern(&self, pattern: &MediaType) -> bool {
+        (pattern.ty == "*" || pattern.ty == self.ty)
+            && (pattern.subtype == "*" || pattern.subtype == self.subtype)
+            && self.has_all_params(&pattern.params)
+    }
+
+    /// true if every of the given parameters is present with the same value
+    fn has_all_params(&self, params: &[(String, String)]) -> bool {
+        params.iter().all(|other| {
+            self.params.iter().any(|param| {
+                param.0.eq_ignore_ascii_case(&other.0)
+                    && param_value_eq(&param.0, &param.1, &other.1)
+            })
+        })
+    }
+
+    /// true if type and subtype are the same, parameters are not compared
+    pub fn eq_ignoring_params(&self, other: &MediaType) -> bool {
+        self.ty == other.ty && self.subtype == other.subtype
+    }
+
+    /// the parameters with lower case names, sorted for comparison
+    ///
+    /// The value of a `charset` parameter is lower cased, too, as
+    /// charset names are case insensitive.
+    fn comparable_params<'a>(&'a self) -> Vec<(String, Cow<'a, str>)> {
+        let mut params = self.params.iter()
+            .map(|param| {
+                let name = param.0.to_ascii_lowercase();
+                let value = if name == "charset" {
+                    Cow::Owned(param.1.to_ascii_lowercase())
+                } else {
+                    Cow::Borrowed(&*param.1)
+                };
+                (name, value)
+            })
+            .collect::<Vec<_>>();
+        params.sort();
+        params
+    }
+}
+
+/// compares type, subtype and parameters
+///
+/// Type, subtype and parameter names are compared case insensitive, parameter
+/// values case sensitive except for the value of `charset`. The order of the
+/// parameters and the grammar used for parsing are ignored.
+impl PartialEq for MediaType {
+    fn eq(&self, other: &MediaType) -> bool {
+        self.eq_ignoring_params(other)
+            && self.params.len() == other.params.len()
+            && self.comparable_params() == other.comparable_params()
+    }
+}
+
+impl Eq for MediaType {}
+
+/// parses a media type using `parse_media_type`
+impl FromStr for MediaType {
+    type Err = MediaTypeError;
+
+    fn from_str(input: &str) -> Result<MediaType, MediaTypeError> {
+        parse_media_type(input)
+    }
+}
+
+/// writes the canonical form of the media type
+///
+/// Type, subtype and parameter names are written in lower case. Parameter values
+/// are only quoted if they are not tokens, using the grammar the media type was
+/// parsed with, so the output can be parsed again with the same grammar.
+impl Display for MediaType {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        write!(fter, "{}/{}", self.ty, self.subtype)?;
+        for param in self.params.iter() {
+            let value = self.grammar.quote_if_needed(&param.1).map_err(|_| fmt::Error)?;
+            write!(fter, "; {}={}", param.0.to_ascii_lowercase(), value)?;
+        }
+        Ok(())
+    }
+}
+
+/// serializes the media type to it's canonical string form (see `Display`)
+#[cfg(feature = "serde")]
+impl Serialize for MediaType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// deserializes a media type from a string using `parse_media_type`
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for MediaType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(MediaTypeVisitor)
+    }
+}
+
+#[cfg(feature = "serde")]
+struct MediaTypeVisitor;
+
+#[cfg(feature = "serde")]
+impl<'de> Visitor<'de> for MediaTypeVisitor {
+    type Value = MediaType;
+
+    fn expecting(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        fter.write_str("a media type")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<MediaType, E> {
+        parse_media_type(value).map_err(E::custom)
+    }
+}
+
+/// compares two values of a parameter with the given name
+///
+/// Values are compared case sensitive except for the value of `charset`.
+fn param_value_eq(name: &str, left: &str, right: &str) -> bool {
+    if name.eq_ignore_ascii_case("charset") {
+        left.eq_ignore_ascii_case(right)
+    } else {
+        left == right
+    }
+}
+
+/// error returned by `MediaTypeBuilder::build`
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum BuildError {
+    /// the type is empty or not a token
+    InvalidType,
+    /// the subtype is empty or not a token
+    InvalidSubtype,
+    /// the parameter name is empty or not a token
+    InvalidParamName(String),
+    /// the parameter value contains chars which can not be represented in a quoted-string
+    InvalidParamValue(String),
+    /// a parameter with the same (case insensitive) name was already added
+    DuplicateParam(String)
+}
+
+impl Display for BuildError {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        use self::BuildError::*;
+        match *self {
+            InvalidType => fter.write_str("type of media type is empty or not a token"),
+            InvalidSubtype => fter.write_str("subtype of media type is empty or not a token"),
+            InvalidParamName(ref name) =>
+                write!(fter, "parameter name {:?} is empty or not a token", name),
+            InvalidParamValue(ref name) =>
+                write!(fter, "value of parameter {:?} contains invalid chars", name),
+            DuplicateParam(ref name) => write!(fter, "duplicate parameter {:?}", name)
+        }
+    }
+}
+
+impl Error for BuildError {}
+
+/// builds a `MediaType` from it's parts
+///
+/// All parts are validated in `build`, so that the resulting media type can always
+/// be written (see `Display`) and parsed again. The media type uses the
+/// internationalized, modern mime grammar (`MimeGrammar::ModernUtf8`).
+///
+/// ```
+/// use media_type_impl_utils::media_type::MediaTypeBuilder;
+///
+/// let mt = MediaTypeBuilder::new("text", "plain")
+///     .param("charset", "utf-8")
+///     .build()
+///     .unwrap();
+/// assert_eq!(mt.to_string(), "text/plain; charset=utf-8");
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct MediaTypeBuilder {
+    ty: String,
+    subtype: String,
+    params: Vec<(String, String)>
+}
+
+impl MediaTypeBuilder {
+
+    /// creates a builder for a media type with given type and subtype
+    pub fn new(type_: &str, subtype: &str) -> Self {
+        MediaTypeBuilder {
+            ty: type_.to_owned(),
+            subtype: subtype.to_owned(),
+            params: Vec::new()
+        }
+    }
+
+    /// adds a parameter, parameters are kept in the order they are added in
+    pub fn param(mut self, name: &str, value: &str) -> Self {
+        self.params.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// validates all parts and creates the media type
+    ///
+    /// # Error
+    ///
+    /// returns the first error found in the order type, subtype, parameters
+    pub fn build(self) -> Result<MediaType, BuildError> {
+        if !is_valid_token::<MimeTokenValidator>(&self.ty) {
+            return Err(BuildError::InvalidType);
+        }
+        if !is_valid_token::<MimeTokenValidator>(&self.subtype) || validate_suffix(&self.subtype).is_err() {
+            return Err(BuildError::InvalidSubtype);
+        }
+        for (idx, (name, value)) in self.params.iter().enumerate() {
+            if !is_valid_token::<MimeTokenValidator>(name) {
+                return Err(BuildError::InvalidParamName(name.clone()));
+            }
+            if classify::<MimeUtf8Quoting>(value).is_invalid() {
+                return Err(BuildError::InvalidParamValue(name.clone()));
+            }
+            if self.params[..idx].iter().any(|other| other.0.eq_ignore_ascii_case(name)) {
+                return Err(BuildError::DuplicateParam(name.clone()));
+            }
+        }
+
+        Ok(MediaType {
+            ty: self.ty.to_ascii_lowercase(),
+            subtype: self.subtype.to_ascii_lowercase(),
+            params: self.params,
+            grammar: MimeGrammar::ModernUtf8
+        })
+    }
+}
+
+/// a media range as used in e.g. the http `Accept` header (e.g. `text/*`)
+///
+/// Like a `MediaType` but type and/or subtype can be the wildcard `*`, which
+/// is represented as `None`. If the type is a wildcard the subtype has to be
+/// one, too. Parameters are kept as for `MediaType` except for the quality
+/// value (`q`) which is not treated as a parameter, see `quality`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct MediaRange {
+    ty: Option<String>,
+    subtype: Option<String>,
+    params: Vec<(String, String)>,
+    /// the quality in thousandths
+    quality: u16
+}
+
+impl MediaRange {
+
+    /// parses a media range using the internationalized, modern mime grammar
+    ///
+    /// # Error
+    ///
+    /// the same errors as `MediaType::parse`, additionally
+    ///
+    /// - `ParseError::InvalidSubtype` if the type is `*` but the subtype is not
+    /// - `ParseError::InvalidQuality` if the `q` parameter is above 1 or has
+    ///   more than three decimal digits
+    pub fn parse(input: &str) -> Result<MediaRange, ParseError> {
+        let media_type = MediaType::parse(input)?;
+        let ty = wildcard_to_none(media_type.ty);
+        let subtype = wildcard_to_none(media_type.subtype);
+        if ty.is_none() && subtype.is_some() {
+            return Err(ParseError::InvalidSubtype);
+        }
+        let mut params = media_type.params;
+        let quality = match params.iter().position(|param| param.0.eq_ignore_ascii_case("q")) {
+            Some(idx) => parse_quality(&params.remove(idx).1).ok_or(ParseError::InvalidQuality)?,
+            None => 1000
+        };
+        Ok(MediaRange { ty, subtype, params, quality })
+    }
+
+    /// returns the quality value (`q`) in the range `0.0` to `1.0`, defaulting to `1.0`
+    pub fn quality(&self) -> f32 {
+        f32::from(self.quality) / 1000.0
+    }
+
+    /// 2 for concrete ranges, 1 for ranges with a wildcard subtype and 0 for `*/*`
+    fn specificity(&self) -> u8 {
+        match (&self.ty, &self.subtype) {
+            (&Some(_), &Some(_)) => 2,
+            (&Some(_), &None) => 1,
+            _ => 0
+        }
+    }
+
+    /// returns the (lower case) type or `None` if it's the wildcard `*`
+    pub fn ty(&self) -> Option<&str> {
+        self.ty.as_deref()
+    }
+
+    /// alias for `ty`
+    pub fn type_(&self) -> Option<&str> {
+        self.ty()
+    }
+
+    /// returns the (lower case) subtype or `None` if it's the wildcard `*`
+    pub fn subtype(&self) -> Option<&str> {
+        self.subtype.as_deref()
+    }
+
+    /// returns the (unquoted) value of the last parameter with the given name
+    ///
+    /// The name is compared case insensitive. If the parameter appears multiple
+    /// times the last occurrence wins (use `parse_strict` to reject duplicates).
+    pub fn get_param(&self, name: &str) -> Option<&str> {
+        self.params.iter().rev()
+            .find(|param| param.0.eq_ignore_ascii_case(name))
+            .map(|param| &*param.1)
+    }
+
+    /// returns a iterator over the parameters in the order they appear in
+    pub fn params<'a>(&'a self) -> impl Iterator<Item=(&'a str, &'a str)> + 'a {
+        self.params.iter().map(|param| (&*param.0, &*param.1))
+    }
+}
+
+/// error returned by `parse_accept`
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct AcceptError {
+    /// the (zero based) index of the invalid element in the list
+    pub index: usize,
+    /// the error parsing the element as `MediaRange` failed with
+    pub error: ParseError
+}
+
+impl Display for AcceptError {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        write!(fter, "invalid media range at position {}: {}", self.index, self.error)
+    }
+}
+
+impl Error for AcceptError {}
+
+/// parses the value of a http `Accept` header
+///
+/// The media ranges are separated by `','`, commas inside of quoted-strings
+/// are not treated as separators. Like for all http lists empty elements
+/// (e.g. from a trailing comma) are ignored, so a empty or whitespace only
+/// input results in a empty list.
+///
+/// The returned media ranges are sorted by descending quality, for the same
+/// quality more specific ranges come first (i.e. `text/html` before `text/*`
+/// before `*/*`). Other than that the order of the input is kept.
+pub fn parse_accept(input: &str) -> Result<Vec<MediaRange>, AcceptError> {
+    let mut ranges = Vec::new();
+    for (index, element) in split_list(input).into_iter().enumerate() {
+        let element = element.trim_matches([' ', '\t']);
+        if element.is_empty() {
+            continue;
+        }
+        let range = MediaRange::parse(element)
+            .map_err(|error| AcceptError { index, error })?;
+        ranges.push(range);
+    }
+    ranges.sort_by(|left, right| {
+        right.quality.cmp(&left.quality)
+            .then_with(|| right.specificity().cmp(&left.specificity()))
+    });
+    Ok(ranges)
+}
+
+/// returns the available media type the client prefers most
+///
+/// The effective quality of a media type is the quality of the most specific
+/// range it matches (a concrete range is more specific than `text/*` which is
+/// more specific than `*/*`, for the same specificity the range with more
+/// parameters wins). Media types matching no range or only ranges with quality
+/// 0 are not acceptable. Ties are broken by the order of `available`.
+// `Option::is_none_or` would raise the minimal rust version to 1.82
+#[allow(clippy::unnecessary_map_or)]
+pub fn best_match<'a>(available: &'a [MediaType], accept: &[MediaRange]) -> Option<&'a MediaType> {
+    let mut best: Option<(&MediaType, u16)> = None;
+    for media_type in available {
+        let quality = accept.iter()
+            .filter(|range| media_type.matches(range))
+            .max_by_key(|range| (range.specificity(), range.params.len()))
+            .map(|range| range.quality);
+        if let Some(quality) = quality {
+            if quality > 0 && best.map_or(true, |best| quality > best.1) {
+                best = Some((media_type, quality));
+            }
+        }
+    }
+    best.map(|best| best.0)
+}
+
+/// splits a comma separated list, ignoring commas in quoted-strings
+///
+/// A unterminated quoted-string extends to the end of the input, it
+/// is up to the parser of the element to reject it.
+fn split_list(input: &str) -> Vec<&str> {
+    let mut elements = Vec::new();
+    let mut start = 0;
+    let mut in_quoted = false;
+    let mut escaped = false;
+    for (idx, bch) in input.bytes().enumerate() {
+        if escaped {
+            escaped = false;
+        } else if in_quoted {
+            match bch {
+                b'\\' => escaped = true,
+                b'"' => in_quoted = false,
+                _ => {}
+            }
+        } else if bch == b'"' {
+            in_quoted = true;
+        } else if bch == b',' {
+            elements.push(&input[start..idx]);
+            start = idx + 1;
+        }
+    }
+    elements.push(&input[start..]);
+    elements
+}
+
+/// parses a quality value (RFC 7231) returning it in thousandths
+///
+/// `qvalue = ( "0" [ "." 0*3DIGIT ] ) / ( "1" [ "." 0*3("0") ] )`
+fn parse_quality(value: &str) -> Option<u16> {
+    let (int, fraction) = match value.find('.') {
+        Some(idx) => (&value[..idx], &value[idx+1..]),
+        None => (value, "")
+    };
+    if fraction.len() > 3 || !fraction.bytes().all(|bch| bch.is_ascii_digit()) {
+        return None;
+    }
+    let thousandths = fraction.bytes()
+        .chain(b"000".iter().cloned())
+        .take(3)
+        .fold(0, |acc, bch| acc * 10 + u16::from(bch - b'0'));
+    match int {
+        "0" => Some(thousandths),
+        "1" if thousandths == 0 => Some(1000),
+        _ => None
+    }
+}
+
+fn wildcard_to_none(part: String) -> Option<String> {
+    if part == "*" {
+        None
+    } else {
+        Some(part)
+    }
+}
+
+/// validates the (unquoted) value of a multipart `boundary` parameter (RFC 2046)
+///
+/// The boundary has to consist of 1 to 70 `BChars` and must not end with a space.
+///
+/// # Error
+///
+/// returns `ParseError::InvalidBoundary` if the boundary is not valid
+pub fn validate_boundary(value: &str) -> Result<(), ParseError> {
+    if is_valid_token::<BoundaryValidator>(value) {
+        Ok(())
+    } else {
+        Err(ParseError::InvalidBoundary)
+    }
+}
+
+/// parses the parameters of a media type reassembling RFC 2231 continuations
+///
+/// The input has the same form as for `Params` (i.e. `; name=value ...`), the
+/// internationalized, modern mime grammar is used for quoted-strings. Sections of
+/// a parameter (`name*0`, `name*1`, ...) are merged by their base name and index
+/// and extended values are decoded, see `reassemble_continuations`.
+///
+/// # Error
+///
+/// besides any error `Params` can return `ParseError::InvalidContinuation` is
+/// returned if e.g. a section is missing or appears multiple times
+pub fn collect_params(input: &str) -> Result<Vec<(String, String)>, ParseError> {
+    let raw_params = RawParams::new(input, MimeGrammar::ModernUtf8)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| err.kind)?;
+    reassemble_continuations(&raw_params).map_err(ParseError::InvalidContinuation)
+}
+
+/// iterator over the parameters of a media type yielding unquoted values
+///
+/// The input is expected to be the part of a media type following the subtype,
+/// i.e. a sequence of `; name=value` where value is a token or a quoted-string wrt.
+/// the given grammar and optional whitespace is allowed around `';'` and `'='`.
+///
+/// The parameter names are returned as is, the values are unquoted using `unquote_cow`
+/// so they are only allocated if they contain quoted-pairs (or FWS). The first error
+/// is returned as `Err` element after which the iterator is fused.
+#[derive(Debug, Clone)]
+pub struct Params<'a> {
+    raw: RawParams<'a>
+}
+
+impl<'a> Params<'a> {
+    /// creates a iterator over the parameters in the given input
+    pub fn new(params: &'a str, grammar: MimeGrammar) -> Self {
+        Params { raw: RawParams::new(params, grammar) }
+    }
+}
+
+impl<'a> Iterator for Params<'a> {
+    type Item = Result<(&'a str, Cow<'a, str>), ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let grammar = self.raw.grammar;
+        let res = self.raw.next()?.map_err(|err| err.kind).and_then(|(name, raw_value)| {
+            grammar.unquote_cow(raw_value)
+                .map(|value| (name, value))
+                .map_err(ParseError::InvalidParamValue)
+        });
+        if res.is_err() {
+            self.raw.failed = true;
+        }
+        Some(res)
+    }
+}
+
+impl<'a> FusedIterator for Params<'a> {}
+
+/// iterator over the parameters of a media type, the values are not unquoted
+#[derive(Debug, Clone)]
+struct RawParams<'a> {
+    input: &'a str,
+    rest: &'a str,
+    grammar: MimeGrammar,
+    failed: bool
+}
+
+impl<'a> RawParams<'a> {
+    fn new(params: &'a str, grammar: MimeGrammar) -> Self {
+        RawParams { input: params, rest: params, grammar, failed: false }
+    }
+
+    /// the byte offset (in the params input) of the not yet parsed rest
+    fn offset(&self) -> usize {
+        self.offset_of(self.rest)
+    }
+
+    fn offset_of(&self, rest: &str) -> usize {
+        self.input.len() - rest.len()
+    }
+
+    fn error_at(&self, kind: ParseError, rest: &str) -> OffsetError<ParseError> {
+        OffsetError::new(kind, self.offset_of(rest))
+    }
+
+    /// skips ws, in the obs grammars also FWS and comments (i.e. CFWS)
+    fn skip_cfws(&self, mut rest: &'a str) -> Result<&'a str, OffsetError<ParseError>> {
+        if !self.grammar.is_obs() {
+            return Ok(skip_ws(rest));
+        }
+        loop {
+            rest = skip_ws(rest);
+            let bytes = rest.as_bytes();
+            if bytes.starts_with(b"\r\n") && matches!(bytes.get(2), Some(&b' ') | Some(&b'\t')) {
+                rest = &rest[3..];
+            } else if rest.starts_with('(') {
+                let span = CommentParser::parse(rest).map_err(|err| {
+                    let offset = match err {
+                        CommentError::InvalidChar(idx) => self.offset_of(rest) + idx,
+                        _ => self.offset_of(rest)
+                    };
+                    OffsetError::new(ParseError::InvalidComment(err), offset)
+                })?;
+                rest = &rest[span.end..];
+            } else {
+                return Ok(rest);
+            }
+        }
+    }
+
+    fn next_param(&mut self) -> Result<Option<(&'a str, &'a str)>, OffsetError<ParseError>> {
+        let rest = self.skip_cfws(self.rest)?;
+        if rest.is_empty() {
+            return Ok(None);
+        }
+        if !rest.starts_with(';') {
+            return Err(self.error_at(ParseError::ExpectedSemicolon, rest));
+        }
+
+        let rest = self.skip_cfws(&rest[1..])?;
+        let name_len = token_len(rest);
+        if name_len == 0 {
+            return Err(self.error_at(ParseError::InvalidParamName, rest));
+        }
+        let (name, rest) = rest.split_at(name_len);
+
+        let rest = self.skip_cfws(rest)?;
+        if !rest.starts_with('=') {
+            return Err(self.error_at(ParseError::MissingParamValue, rest));
+        }
+
+        let rest = self.skip_cfws(&rest[1..])?;
+        let value_len = if rest.starts_with('"') {
+            quoted_string_len(rest, self.grammar).map_err(|err| {
+                OffsetError::new(ParseError::InvalidParamValue(err.kind), self.offset_of(rest) + err.offset)
+            })?
+        } else {
+            token_len(rest)
+        };
+        if value_len == 0 {
+            return Err(self.error_at(ParseError::MissingParamValue, rest));
+        }
+        let (value, rest) = rest.split_at(value_len);
+
+        self.rest = rest;
+        Ok(Some((name, value)))
+    }
+}
+
+impl<'a> Iterator for RawParams<'a> {
+    type Item = Result<(&'a str, &'a str), OffsetError<ParseError>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        match self.next_param() {
+            Ok(param) => param.map(Ok),
+            Err(err) => {
+                self.failed = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+fn skip_ws(input: &str) -> &str {
+    input.trim_start_matches([' ', '\t'])
+}
+
+/// checks that the structured syntax suffix of the (token) subtype is a restricted-name
+///
+/// On error the index of the first byte of the suffix is returned.
+fn validate_suffix(subtype: &str) -> Result<(), usize> {
+    match subtype.rfind('+') {
+        Some(plus_idx) if !is_valid_token::<MimeRestrictedNameValidator>(&subtype[plus_idx+1..]) =>
+            Err(plus_idx + 1),
+        _ => Ok(())
+    }
+}
+
+/// the length of the longest prefix of input which is a (mime) token
+fn token_len(input: &str) -> usize {
+    let mut validator = MimeTokenValidator::new();
+    input.bytes()
+        .position(|bch| !validator.next(PartialCodePoint::from_utf8_byte(bch)))
+        .unwrap_or(input.len())
+}
+
+/// the length of the quoted-string input starts with
+fn quoted_string_len(input: &str, grammar: MimeGrammar) -> Result<usize, OffsetError<CoreError>> {
+    use self::MimeGrammar::*;
+    match grammar {
+        Modern => quoted_string_end_with_offset::<MimeParsing>(input),
+        ModernUtf8 => quoted_string_end_with_offset::<MimeParsingUtf8>(input),
+        Obs => quoted_string_end_with_offset::<MimeObsParsing>(input),
+        ObsUtf8 => quoted_string_end_with_offset::<MimeObsParsingUtf8>(input)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case)]
+
+    mod MediaType {
+        use qs::error::CoreError;
+        use comment::CommentError;
+        use quoted_string::MimeGrammar;
+        use super::super::{MediaType, ParseError, RegistrationTree, parse_media_type};
+
+        fn params(mt: &MediaType) -> Vec<(&str, &str)> {
+            mt.params().collect()
+        }
+
+        mod get_param {
+            use super::*;
+
+            #[test]
+            fn is_case_insensitive() {
+                let mt = MediaType::parse("text/plain; CHARSET=utf-8; x=\"a\\\\b\"").unwrap();
+                assert_eq!(mt.get_param("charset"), Some("utf-8"));
+                assert_eq!(mt.get_param("X"), Some("a\\b"));
+                assert_eq!(mt.get_param("y"), None);
+            }
+        }
+
+        mod charset {
+            use std::borrow::Cow;
+            use super::*;
+
+            #[test]
+            fn quoted_value_is_decoded_and_lower_cased() {
+                let mt = MediaType::parse("text/plain; charset=\"UTF-8\"").unwrap();
+                assert_eq!(mt.charset().as_deref(), Some("utf-8"));
+                assert!(matches!(mt.charset(), Some(Cow::Owned(_))));
+            }
+
+            #[test]
+            fn name_is_case_insensitive() {
+                let mt = MediaType::parse("text/plain; CharSet=utf-8").unwrap();
+                assert!(matches!(mt.charset(), Some(Cow::Borrowed("utf-8"))));
+            }
+
+            #[test]
+            fn upper_case_value() {
+                let mt = MediaType::parse("text/html; charset=UTF-8").unwrap();
+                assert_eq!(mt.charset().as_deref(), Some("utf-8"));
+                assert_eq!(mt.get_param("charset"), Some("UTF-8"));
+            }
+
+            #[test]
+            fn missing() {
+                let mt = MediaType::parse("text/plain; format=flowed").unwrap();
+                assert_eq!(mt.charset(), None);
+            }
+        }
+
+        mod boundary {
+            use super::*;
+
+            #[test]
+            fn present() {
+                let mt = MediaType::parse("multipart/mixed; boundary=abc").unwrap();
+                assert_eq!(mt.boundary(), Some("abc"));
+            }
+
+            #[test]
+            fn name_is_case_insensitive_value_is_not_changed() {
+                let mt = MediaType::parse("multipart/mixed; Boundary=\"AbC d\"").unwrap();
+                assert_eq!(mt.boundary(), Some("AbC d"));
+            }
+
+            #[test]
+            fn missing() {
+                let mt = MediaType::parse("multipart/mixed; charset=utf-8").unwrap();
+                assert_eq!(mt.boundary(), None);
+            }
+        }
+
+        mod suffix {
+            use super::*;
+
+            #[test]
+            fn with_suffix() {
+                let mt = MediaType::parse("image/svg+xml").unwrap();
+                assert_eq!(mt.suffix(), Some("xml"));
+                assert_eq!(mt.without_suffix(), "svg");
+            }
+
+            #[test]
+            fn without_suffix() {
+                let mt = MediaType::parse("application/json").unwrap();
+                assert_eq!(mt.suffix(), None);
+                assert_eq!(mt.without_suffix(), "json");
+            }
+
+            #[test]
+            fn only_last_suffix() {
+                let mt = MediaType::parse("application/a+b+c").unwrap();
+                assert_eq!(mt.suffix(), Some("c"));
+                assert_eq!(mt.without_suffix(), "a+b");
+            }
+
+            #[test]
+            fn base_subtype() {
+                let mt = MediaType::parse("application/ld+json").unwrap();
+                assert_eq!(mt.suffix(), Some("json"));
+                assert_eq!(mt.base_subtype(), "ld");
+                let mt = MediaType::parse("application/json").unwrap();
+                assert_eq!(mt.base_subtype(), "json");
+            }
+
+            #[test]
+            fn trailing_plus_is_rejected() {
+                let err = MediaType::parse("application/foo+").unwrap_err();
+                assert_eq!(err, ParseError::InvalidSubtype);
+                let err = MediaType::parse("application/foo+; charset=utf-8").unwrap_err();
+                assert_eq!(err, ParseError::InvalidSubtype);
+            }
+
+            #[test]
+            fn suffix_has_to_be_restricted_name() {
+                let err = MediaType::parse("application/foo+{json}").unwrap_err();
+                assert_eq!(err, ParseError::InvalidSubtype);
+                let err = MediaType::parse("application/foo+-json").unwrap_err();
+                assert_eq!(err, ParseError::InvalidSubtype);
+            }
+        }
+
+        mod registration_tree {
+            use super::*;
+
+            fn tree_of(input: &str) -> RegistrationTree {
+                MediaType::parse(input).unwrap().registration_tree()
+            }
+
+            #[test]
+            fn trees() {
+                assert_eq!(tree_of("application/vnd.api+json"), RegistrationTree::Vendor);
+                assert_eq!(tree_of("application/prs.foo"), RegistrationTree::Personal);
+                assert_eq!(tree_of("application/x-tar"), RegistrationTree::Unregistered);
+                assert_eq!(tree_of("application/x.foo"), RegistrationTree::Unregistered);
+                assert_eq!(tree_of("text/plain"), RegistrationTree::Standards);
+            }
+
+            #[test]
+            fn prefix_is_case_insensitive() {
+                assert_eq!(tree_of("application/VND.foo"), RegistrationTree::Vendor);
+            }
+
+            #[test]
+            fn prefix_needs_separator() {
+                assert_eq!(tree_of("application/vndfoo"), RegistrationTree::Standards);
+                assert_eq!(tree_of("application/xml"), RegistrationTree::Standards);
+            }
+        }
+
+        mod type_predicates {
+            use super::*;
+
+            fn mt(input: &str) -> MediaType {
+                MediaType::parse(input).unwrap()
+            }
+
+            #[test]
+            fn is_text() {
+                assert!(mt("Text/HTML").is_text());
+                assert!(!mt("application/text").is_text());
+            }
+
+            #[test]
+            fn is_multipart() {
+                assert!(mt("multipart/form-data; boundary=x").is_multipart());
+                assert!(!mt("message/rfc822").is_multipart());
+            }
+
+            #[test]
+            fn is_image() {
+                assert!(mt("image/png").is_image());
+                assert!(!mt("video/png").is_image());
+            }
+
+            #[test]
+            fn is_utf8_compatible() {
+                assert!(mt("text/plain").is_utf8_compatible());
+                assert!(mt("application/json").is_utf8_compatible());
+                assert!(mt("application/ld+json").is_utf8_compatible());
+                assert!(mt("application/xml; charset=UTF-8").is_utf8_compatible());
+                assert!(!mt("text/plain; charset=utf-16").is_utf8_compatible());
+                assert!(!mt("application/octet-stream").is_utf8_compatible());
+                assert!(!mt("image/svg+xml").is_utf8_compatible());
+            }
+        }
+
+        mod params {
+            use super::*;
+
+            #[test]
+            fn order_and_content_is_preserved() {
+                let mt = MediaType::parse("text/plain; z=1; a=\"2\"; m=3").unwrap();
+                let mut iter = mt.params();
+                assert_eq!(iter.next(), Some(("z", "1")));
+                assert_eq!(iter.next(), Some(("a", "2")));
+                assert_eq!(iter.next(), Some(("m", "3")));
+                assert_eq!(iter.next(), None);
+            }
+
+            #[test]
+            fn param_names() {
+                let mt = MediaType::parse("text/plain; z=1; a=\"2\"; m=3").unwrap();
+                assert_eq!(mt.param_names().collect::<Vec<_>>(), vec!["z", "a", "m"]);
+            }
+        }
+
+        mod normalize {
+            use super::*;
+
+            #[test]
+            fn lower_cases_and_sorts_params() {
+                let mt = MediaType::parse("Application/JSON; B=2; A=1").unwrap().normalized();
+                assert_eq!(mt.type_(), "application");
+                assert_eq!(mt.subtype(), "json");
+                assert_eq!(params(&mt), vec![("a", "1"), ("b", "2")]);
+            }
+
+            #[test]
+            fn values_keep_their_case() {
+                let mut mt = MediaType::parse("text/plain; X=ABC; Charset=UTF-8").unwrap();
+                mt.normalize();
+                assert_eq!(params(&mt), vec![("charset", "UTF-8"), ("x", "ABC")]);
+                assert_eq!(mt.charset().as_deref(), Some("utf-8"));
+            }
+
+            #[test]
+            fn duplicate_names_keep_last() {
+                let mt = MediaType::parse("text/plain; b=2; a=3; B=1").unwrap().normalized();
+                assert_eq!(params(&mt), vec![("a", "3"), ("b", "1")]);
+            }
+
+            #[test]
+            fn differently_ordered_and_cased_inputs_normalize_equal() {
+                let left = MediaType::parse("TEXT/Plain; Format=Flowed; charset=UTF-8").unwrap();
+                let right = MediaType::parse("text/PLAIN; Charset=UTF-8; FORMAT=fixed; format=Flowed").unwrap();
+                let (left, right) = (left.normalized(), right.normalized());
+                assert_eq!(params(&left), params(&right));
+                assert_eq!(left.to_string(), "text/plain; charset=UTF-8; format=Flowed");
+                assert_eq!(left.to_string(), right.to_string());
+                let lower = MediaType::parse("text/plain; charset=utf-8; format=Flowed").unwrap();
+                assert_eq!(left, lower.normalized());
+            }
+        }
+
+        mod display {
+            use super::*;
+
+            #[test]
+            fn canonical_form() {
+                let mt = MediaType::parse("Text/Plain ;Charset = \"utf-8\"; X=\"a b\"").unwrap();
+                assert_eq!(mt.to_string(), "text/plain; charset=utf-8; x=\"a b\"");
+            }
+
+            #[test]
+            fn value_with_space_is_quoted() {
+                let mt = MediaType::parse("Application/X-Test; Title=\"a b\"; Charset=utf-8").unwrap();
+                let out = mt.to_string();
+                assert_eq!(out, "application/x-test; title=\"a b\"; charset=utf-8");
+                assert_eq!(parse_media_type(&out).unwrap(), mt);
+            }
+
+            #[test]
+            fn round_trips() {
+                let mt = MediaType::parse(r#"multipart/mixed; boundary="a\"b"; name="ä""#).unwrap();
+                let reparsed = MediaType::parse(&mt.to_string()).unwrap();
+                assert_eq!(mt, reparsed);
+                assert_eq!(params(&reparsed), vec![("boundary", "a\"b"), ("name", "ä")]);
+            }
+        }
+
+        mod from_str {
+            use super::*;
+
+            #[test]
+            fn same_as_parse() {
+                let mt: MediaType = "text/plain; charset=utf-8".parse().unwrap();
+                assert_eq!(mt, MediaType::parse("text/plain; charset=utf-8").unwrap());
+                assert_eq!("text".parse::<MediaType>().unwrap_err(), ParseError::MissingSlash);
+            }
+
+            #[test]
+            fn err_is_media_type_error() {
+                use super::super::super::MediaTypeError;
+                let err: MediaTypeError = "textplain".parse::<MediaType>().unwrap_err();
+                assert_eq!(err, MediaTypeError::MissingSlash);
+            }
+
+            #[test]
+            fn trailing_junk_after_params() {
+                let err = "text/plain; charset=utf-8 junk".parse::<MediaType>().unwrap_err();
+                assert_eq!(err, ParseError::ExpectedSemicolon);
+                let err = "text/plain; title=\"a\"b".parse::<MediaType>().unwrap_err();
+                assert_eq!(err, ParseError::ExpectedSemicolon);
+            }
+
+            #[test]
+            fn round_trip_normalizes_case_and_quoting() {
+                let inputs = [
+                    ("TEXT/Plain; Charset=\"utf-8\"", "text/plain; charset=utf-8"),
+                    ("text/plain;charset=utf-8", "text/plain; charset=utf-8"),
+                    ("Multipart/Mixed; Boundary=\"a b\"", "multipart/mixed; boundary=\"a b\""),
+                    ("application/x; a=\"\\x\"", "application/x; a=x"),
+                ];
+                for &(input, expected) in inputs.iter() {
+                    let out = input.parse::<MediaType>().unwrap().to_string();
+                    assert_eq!(out, expected);
+                    assert_eq!(out.parse::<MediaType>().unwrap().to_string(), expected);
+                }
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        mod serde {
+            use serde_json;
+            use super::*;
+
+            #[test]
+            fn round_trip() {
+                let mt = MediaType::parse("application/vnd.api+json; charset=utf-8").unwrap();
+                let json = serde_json::to_string(&mt).unwrap();
+                assert_eq!(json, "\"application/vnd.api+json; charset=utf-8\"");
+                let back: MediaType = serde_json::from_str(&json).unwrap();
+                assert_eq!(back, mt);
+            }
+
+            #[test]
+            fn round_trip_with_quoted_parameters() {
+                let mt = MediaType::parse("text/plain; charset=\"utf-8\"").unwrap();
+                let json = serde_json::to_string(&mt).unwrap();
+                assert_eq!(json, "\"text/plain; charset=utf-8\"");
+                let back: MediaType = serde_json::from_str(&json).unwrap();
+                assert_eq!(back, mt);
+
+                let mt = MediaType::parse("text/plain; title=\"a b\"").unwrap();
+                let json = serde_json::to_string(&mt).unwrap();
+                assert_eq!(json, r#""text/plain; title=\"a b\"""#);
+                let back: MediaType = serde_json::from_str(&json).unwrap();
+                assert_eq!(back, mt);
+                assert_eq!(back.get_param("title"), Some("a b"));
+            }
+
+            #[test]
+            fn invalid_string_is_a_serde_error() {
+                let err = serde_json::from_str::<MediaType>("\"text\"").unwrap_err();
+                assert!(err.to_string().contains(&ParseError::MissingSlash.to_string()));
+            }
+        }
+
+        mod eq {
+            use super::*;
+
+            #[test]
+            fn case_insensitive_type_subtype_names_and_charset() {
+                let left = MediaType::parse("Text/Plain; Charset=UTF-8").unwrap();
+                let right = MediaType::parse("text/plain; charset=utf-8").unwrap();
+                assert_eq!(left, right);
+            }
+
+            #[test]
+            fn other_values_are_case_sensitive() {
+                let left = MediaType::parse("application/json; q=1").unwrap();
+                let right = MediaType::parse("application/json; q=2").unwrap();
+                assert_ne!(left, right);
+                let left = MediaType::parse("multipart/mixed; boundary=abc").unwrap();
+                let right = MediaType::parse("multipart/mixed; boundary=ABC").unwrap();
+                assert_ne!(left, right);
+            }
+
+            #[test]
+            fn param_order_is_ignored() {
+                let left = MediaType::parse("text/plain; a=1; b=2").unwrap();
+                let right = MediaType::parse("text/plain; b=2; a=\"1\"").unwrap();
+                assert_eq!(left, right);
+            }
+
+            #[test]
+            fn missing_param_differs() {
+                let left = MediaType::parse("text/plain; a=1").unwrap();
+                let right = MediaType::parse("text/plain").unwrap();
+                assert_ne!(left, right);
+            }
+
+            #[test]
+            fn eq_ignoring_params() {
+                let left = MediaType::parse("application/json; q=1").unwrap();
+                let right = MediaType::parse("Application/JSON; q=2").unwrap();
+                assert!(left.eq_ignoring_params(&right));
+                let other = MediaType::parse("application/xml").unwrap();
+                assert!(!left.eq_ignoring_params(&other));
+            }
+        }
+
+        mod matches {
+            use super::*;
+            use super::super::super::MediaRange;
+
+            fn matches(media_type: &str, range: &str) -> bool {
+                let media_type = MediaType::parse(media_type).unwrap();
+                media_type.matches(&MediaRange::parse(range).unwrap())
+            }
+
+            #[test]
+            fn wildcard_subtype() {
+                assert!(matches("text/html", "text/*"));
+                assert!(!matches("image/png", "text/*"));
+            }
+
+            #[test]
+            fn full_wildcard() {
+                assert!(matches("text/html", "*/*"));
+                assert!(matches("application/json; charset=utf-8", "*/*"));
+            }
+
+            #[test]
+            fn concrete_range() {
+                assert!(!matches("text/html", "text/plain"));
+                assert!(matches("text/html", "Text/HTML"));
+            }
+
+            #[test]
+            fn range_params_have_to_be_present() {
+                assert!(matches("text/html; level=1; charset=utf-8", "text/html; level=1"));
+                assert!(matches("text/html; charset=utf-8", "text/*; Charset=UTF-8"));
+                assert!(!matches("text/html; level=2", "text/html; level=1"));
+                assert!(!matches("text/html", "text/html; level=1"));
+            }
+        }
+
+        mod matches_pattern {
+            use super::*;
+
+            fn matches(media_type: &str, pattern: &str) -> bool {
+                let media_type = MediaType::parse(media_type).unwrap();
+                media_type.matches_pattern(&MediaType::parse(pattern).unwrap())
+            }
+
+            #[test]
+            fn wildcard_subtype() {
+                assert!(matches("text/html", "text/*"));
+                assert!(matches("text/html", "TEXT/*"));
+                assert!(!matches("image/png", "text/*"));
+            }
+
+            #[test]
+            fn full_wildcard() {
+                assert!(matches("text/html", "*/*"));
+                assert!(matches("application/vnd.api+json; charset=utf-8", "*/*"));
+            }
+
+            #[test]
+            fn wildcard_type() {
+                assert!(matches("application/json", "*/json"));
+                assert!(!matches("application/xml", "*/json"));
+            }
+
+            #[test]
+            fn concrete_pattern() {
+                assert!(matches("text/html", "Text/HTML"));
+                assert!(!matches("text/html", "text/plain"));
+            }
+
+            #[test]
+            fn pattern_params_have_to_be_present() {
+                assert!(matches("text/html; level=1; charset=utf-8", "text/*; Charset=UTF-8"));
+                assert!(!matches("text/html; charset=utf-8", "text/*; charset=us-ascii"));
+                assert!(!matches("text/html", "*/*; level=1"));
+            }
+        }
+
+        mod parse {
+            use super::*;
+
+            #[test]
+            fn type_and_subtype() {
+                let mt = MediaType::parse("text/plain").unwrap();
+                assert_eq!(mt.ty(), "text");
+                assert_eq!(mt.subtype(), "plain");
+                assert_eq!(params(&mt), vec![]);
+            }
+
+            #[test]
+            fn with_params() {
+                let mt = MediaType::parse("multipart/mixed; boundary=\"a b\";charset=utf-8").unwrap();
+                assert_eq!(mt.ty(), "multipart");
+                assert_eq!(mt.subtype(), "mixed");
+                assert_eq!(params(&mt), vec![("boundary", "a b"), ("charset", "utf-8")]);
+            }
+
+            #[test]
+            fn comments_between_params_in_obs_grammar() {
+                let input = "text/plain; (legacy mta) charset=utf-8 (a (nested) comment); a = (x\\)) b";
+                let mt = MediaType::parse_with(input, MimeGrammar::Obs).unwrap();
+                assert_eq!(params(&mt), vec![("charset", "utf-8"), ("a", "b")]);
+                let mt = MediaType::parse_with("text/plain;\r\n charset=utf-8", MimeGrammar::ObsUtf8).unwrap();
+                assert_eq!(params(&mt), vec![("charset", "utf-8")]);
+            }
+
+            #[test]
+            fn comments_are_rejected_in_modern_grammar() {
+                let err = MediaType::parse_with("text/plain; (c) charset=utf-8", MimeGrammar::Modern);
+                assert_eq!(err.unwrap_err(), ParseError::InvalidParamName);
+            }
+
+            #[test]
+            fn malformed_comments() {
+                let err = MediaType::parse_with_offset("text/plain; (a (b) a=b", MimeGrammar::Obs).unwrap_err();
+                assert_eq!(err.kind, ParseError::InvalidComment(CommentError::Unterminated));
+                assert_eq!(err.offset, 12);
+                let err = MediaType::parse_with("text/plain; a=b (\r)", MimeGrammar::Obs).unwrap_err();
+                assert_eq!(err, ParseError::InvalidComment(CommentError::InvalidChar(2)));
+            }
+
+            #[test]
+            fn ws_around_separators() {
+                let mt = MediaType::parse("text/plain ; charset = \"utf-8\" \t").unwrap();
+                assert_eq!(mt.subtype(), "plain");
+                assert_eq!(params(&mt), vec![("charset", "utf-8")]);
+            }
+
+            #[test]
+            fn quoted_string_with_separators() {
+                let mt = MediaType::parse(r#"text/plain; x="a;b=\"c\"""#).unwrap();
+                assert_eq!(params(&mt), vec![("x", r#"a;b="c""#)]);
+            }
+
+            #[test]
+            fn invalid_type_or_subtype() {
+                assert_eq!(MediaType::parse("/plain").unwrap_err(), ParseError::InvalidType);
+                assert_eq!(MediaType::parse("te xt/plain").unwrap_err(), ParseError::InvalidType);
+                assert_eq!(MediaType::parse("text").unwrap_err(), ParseError::MissingSlash);
+                assert_eq!(MediaType::parse("text/").unwrap_err(), ParseError::InvalidSubtype);
+                assert_eq!(MediaType::parse("text/pl@in").unwrap_err(), ParseError::InvalidSubtype);
+            }
+
+            #[test]
+            fn invalid_params() {
+                let err = MediaType::parse("text/plain; =utf-8").unwrap_err();
+                assert_eq!(err, ParseError::InvalidParamName);
+                let err = MediaType::parse("text/plain;").unwrap_err();
+                assert_eq!(err, ParseError::InvalidParamName);
+                let err = MediaType::parse("text/plain; charset").unwrap_err();
+                assert_eq!(err, ParseError::MissingParamValue);
+                let err = MediaType::parse("text/plain; charset=").unwrap_err();
+                assert_eq!(err, ParseError::MissingParamValue);
+                let err = MediaType::parse("text/plain; a=b c=d").unwrap_err();
+                assert_eq!(err, ParseError::ExpectedSemicolon);
+                let err = MediaType::parse("text/plain; a=\"b").unwrap_err();
+                assert_eq!(err, ParseError::InvalidParamValue(CoreError::DoesNotEndWithDQuotes));
+            }
+
+            #[test]
+            fn type_and_subtype_are_normalized() {
+                let mt = MediaType::parse("Text/HTML; Charset=\"utf-8\"").unwrap();
+                assert_eq!(mt.type_(), "text");
+                assert_eq!(mt.subtype(), "html");
+                assert_eq!(mt.get_param("charset"), Some("utf-8"));
+                assert_eq!(params(&mt), vec![("Charset", "utf-8")]);
+            }
+
+            #[test]
+            fn grammar_is_used_for_values() {
+                assert!(MediaType::parse("text/plain; a=\"ä\"").is_ok());
+                let err = MediaType::parse_with("text/plain; a=\"ä\"", MimeGrammar::Modern).unwrap_err();
+                assert_eq!(err, ParseError::InvalidParamValue(CoreError::InvalidChar));
+            }
+        }
+
+        mod parse_strict {
+            use super::*;
+
+            #[test]
+            fn unique_params() {
+                let mt = MediaType::parse_strict("text/plain; charset=utf-8; format=flowed").unwrap();
+                assert_eq!(params(&mt), vec![("charset", "utf-8"), ("format", "flowed")]);
+            }
+
+            #[test]
+            fn repeated_charset_is_rejected() {
+                let err = MediaType::parse_strict("text/plain; charset=utf-8; Charset=us-ascii").unwrap_err();
+                assert_eq!(err, ParseError::DuplicateParameter { name: "Charset".to_owned() });
+            }
+
+            #[test]
+            fn repeated_charset_is_accepted_by_lenient_parse() {
+                let mt = MediaType::parse("text/plain; charset=utf-8; Charset=us-ascii").unwrap();
+                assert_eq!(params(&mt), vec![("charset", "utf-8"), ("Charset", "us-ascii")]);
+                assert_eq!(mt.get_param("charset"), Some("us-ascii"));
+                assert_eq!(mt.charset().as_deref(), Some("us-ascii"));
+            }
+
+            #[test]
+            fn with_obs_grammar() {
+                let input = "text/plain; charset=utf-8 (comment); CHARSET=us-ascii";
+                let err = MediaType::parse_strict_with(input, MimeGrammar::Obs).unwrap_err();
+                assert_eq!(err, ParseError::DuplicateParameter { name: "CHARSET".to_owned() });
+                let mt = MediaType::parse_strict_with("text/plain; charset=\"a\r\n b\"", MimeGrammar::Obs).unwrap();
+                assert_eq!(mt.charset().as_deref(), Some("a b"));
+            }
+
+            #[test]
+            fn other_errors_are_kept() {
+                assert_eq!(MediaType::parse_strict("text"), Err(ParseError::MissingSlash));
+            }
+        }
+
+        mod parse_with_offset {
+            use super::*;
+            use quoted_string::OffsetError;
+
+            fn parse_err(input: &str, grammar: MimeGrammar) -> OffsetError<ParseError> {
+                MediaType::parse_with_offset(input, grammar).unwrap_err()
+            }
+
+            #[test]
+            fn invalid_char_mid_value() {
+                let err = parse_err("text/plain; a=\"bc\x07d\"", MimeGrammar::ModernUtf8);
+                assert_eq!(err, OffsetError::new(ParseError::InvalidParamValue(CoreError::InvalidChar), 17));
+            }
+
+            #[test]
+            fn points_at_first_byte_of_multi_byte_char() {
+                let err = parse_err("text/plain; a=\"bcäd\"", MimeGrammar::Modern);
+                assert_eq!(err, OffsetError::new(ParseError::InvalidParamValue(CoreError::InvalidChar), 17));
+            }
+
+            #[test]
+            fn offsets_of_structural_errors() {
+                let err = parse_err("te@t/plain", MimeGrammar::ModernUtf8);
+                assert_eq!(err, OffsetError::new(ParseError::InvalidType, 2));
+                let err = parse_err("text/pl@in", MimeGrammar::ModernUtf8);
+                assert_eq!(err, OffsetError::new(ParseError::InvalidSubtype, 7));
+                let err = parse_err("text/plain; a=b c=d", MimeGrammar::ModernUtf8);
+                assert_eq!(err, OffsetError::new(ParseError::ExpectedSemicolon, 16));
+                let err = parse_err("text/plain; =b", MimeGrammar::ModernUtf8);
+                assert_eq!(err, OffsetError::new(ParseError::InvalidParamName, 12));
+            }
+
+            #[test]
+            fn unterminated_quoted_string() {
+                let err = parse_err("text/plain; charset=\"ut", MimeGrammar::ModernUtf8);
+                let kind = ParseError::InvalidParamValue(CoreError::DoesNotEndWithDQuotes);
+                assert_eq!(err, OffsetError::new(kind, 20));
+            }
+
+            #[test]
+            fn illegal_control_char() {
+                let err = parse_err("text/plain; charset=\"ut\x01f-8\"", MimeGrammar::ModernUtf8);
+                assert_eq!(err, OffsetError::new(ParseError::InvalidParamValue(CoreError::InvalidChar), 23));
+                let err = parse_err("text/plain; charset=ut\x01f-8", MimeGrammar::ModernUtf8);
+                assert_eq!(err, OffsetError::new(ParseError::ExpectedSemicolon, 22));
+            }
+
+            #[test]
+            fn agrees_with_parse_with() {
+                let input = "text/plain; a=\"b\x07\"";
+                let err = parse_err(input, MimeGrammar::ModernUtf8);
+                assert_eq!(MediaType::parse_with(input, MimeGrammar::ModernUtf8).unwrap_err(), err.kind);
+            }
+        }
+    }
+
+    mod MediaTypeBuilder {
+        use super::super::{MediaTypeBuilder, BuildError, parse_media_type};
+
+        #[test]
+        fn valid_build() {
+            let mt = MediaTypeBuilder::new("Text", "Plain")
+                .param("charset", "utf-8")
+                .param("title", "a \"b\"")
+                .build()
+                .unwrap();
+            assert_eq!(mt.type_(), "text");
+            assert_eq!(mt.subtype(), "plain");
+            assert_eq!(mt.get_param("title"), Some("a \"b\""));
+            assert_eq!(parse_media_type(&mt.to_string()).unwrap(), mt);
+        }
+
+        #[test]
+        fn duplicate_param() {
+            let res = MediaTypeBuilder::new("text", "plain")
+                .param("charset", "utf-8")
+                .param("Charset", "us-ascii")
+                .build();
+            assert_eq!(res, Err(BuildError::DuplicateParam("Charset".to_owned())));
+        }
+
+        #[test]
+        fn invalid_type_and_subtype() {
+            assert_eq!(MediaTypeBuilder::new("", "plain").build(), Err(BuildError::InvalidType));
+            assert_eq!(MediaTypeBuilder::new("text", "pl ain").build(), Err(BuildError::InvalidSubtype));
+            assert_eq!(MediaTypeBuilder::new("text", "").build(), Err(BuildError::InvalidSubtype));
+        }
+
+        #[test]
+        fn invalid_suffix() {
+            assert_eq!(MediaTypeBuilder::new("application", "foo+").build(), Err(BuildError::InvalidSubtype));
+            assert_eq!(MediaTypeBuilder::new("application", "foo+-x").build(), Err(BuildError::InvalidSubtype));
+            assert!(MediaTypeBuilder::new("application", "foo+json").build().is_ok());
+        }
+
+        #[test]
+        fn invalid_param() {
+            let res = MediaTypeBuilder::new("text", "plain").param("a=b", "c").build();
+            assert_eq!(res, Err(BuildError::InvalidParamName("a=b".to_owned())));
+            let res = MediaTypeBuilder::new("text", "plain").param("x", "a\x01b").build();
+            assert_eq!(res, Err(BuildError::InvalidParamValue("x".to_owned())));
+        }
+    }
+
+    mod MediaRange {
+        use super::super::{MediaType, MediaRange, ParseError};
+
+        #[test]
+        fn concrete() {
+            let range = MediaRange::parse("Text/HTML; level=1").unwrap();
+            assert_eq!(range.type_(), Some("text"));
+            assert_eq!(range.subtype(), Some("html"));
+            assert_eq!(range.get_param("Level"), Some("1"));
+        }
+
+        #[test]
+        fn wildcards() {
+            let range = MediaRange::parse("text/*").unwrap();
+            assert_eq!(range.type_(), Some("text"));
+            assert_eq!(range.subtype(), None);
+            let range = MediaRange::parse("*/*").unwrap();
+            assert_eq!(range.ty(), None);
+            assert_eq!(range.subtype(), None);
+        }
+
+        #[test]
+        fn wildcard_type_needs_wildcard_subtype() {
+            assert_eq!(MediaRange::parse("*/html"), Err(ParseError::InvalidSubtype));
+        }
+
+        #[test]
+        fn quality() {
+            assert_eq!(MediaRange::parse("text/html; q=0.8").unwrap().quality(), 0.8);
+            assert_eq!(MediaRange::parse("text/html").unwrap().quality(), 1.0);
+            assert_eq!(MediaRange::parse("text/html;Q=0").unwrap().quality(), 0.0);
+            assert_eq!(MediaRange::parse("text/html;q=1.000").unwrap().quality(), 1.0);
+            assert_eq!(MediaRange::parse("text/html;q=0.125").unwrap().quality(), 0.125);
+        }
+
+        #[test]
+        fn invalid_quality() {
+            for input in &["text/html; q=1.5", "text/html; q=0.1234", "text/html; q=2",
+                           "text/html; q=.5", "text/html; q=0.x", "text/html; q=1.001"]
+            {
+                assert_eq!(MediaRange::parse(input), Err(ParseError::InvalidQuality), "{}", input);
+            }
+        }
+
+        #[test]
+        fn quality_is_not_a_param() {
+            let range = MediaRange::parse("text/html; q=0.5; level=1").unwrap();
+            assert_eq!(range.get_param("q"), None);
+            assert_eq!(range.params().collect::<Vec<_>>(), vec![("level", "1")]);
+            let media_type = MediaType::parse("text/html; level=1").unwrap();
+            assert!(media_type.matches(&range));
+        }
+    }
+
+    mod parse_accept {
+        use super::super::{parse_accept, AcceptError, MediaRange, ParseError};
+
+        fn ranges(input: &str) -> Vec<(Option<String>, Option<String>, f32)> {
+            parse_accept(input).unwrap()
+                .iter()
+                .map(|range: &MediaRange| (
+                    range.type_().map(ToOwned::to_owned),
+                    range.subtype().map(ToOwned::to_owned),
+                    range.quality()
+                ))
+                .collect()
+        }
+
+        fn range(ty: &str, subtype: &str, quality: f32) -> (Option<String>, Option<String>, f32) {
+            let to_part = |part: &str| if part == "*" { None } else { Some(part.to_owned()) };
+            (to_part(ty), to_part(subtype), quality)
+        }
+
+        #[test]
+        fn browser_accept() {
+            let parsed = ranges("text/html, application/xhtml+xml, application/xml;q=0.9, */*;q=0.8");
+            assert_eq!(parsed, vec![
+                range("text", "html", 1.0),
+                range("application", "xhtml+xml", 1.0),
+                range("application", "xml", 0.9),
+                range("*", "*", 0.8)
+            ]);
+        }
+
+        #[test]
+        fn sorted_by_quality_then_specificity() {
+            let parsed = ranges("*/*, text/*;q=0.5, text/plain;q=0.5, text/html");
+            assert_eq!(parsed, vec![
+                range("text", "html", 1.0),
+                range("*", "*", 1.0),
+                range("text", "plain", 0.5),
+                range("text", "*", 0.5)
+            ]);
+        }
+
+        #[test]
+        fn comma_in_quoted_string() {
+            let parsed = parse_accept(r#"text/html; x="a,\"b,", text/plain"#).unwrap();
+            assert_eq!(parsed.len(), 2);
+            assert_eq!(parsed[0].get_param("x"), Some("a,\"b,"));
+            assert_eq!(parsed[1].subtype(), Some("plain"));
+        }
+
+        #[test]
+        fn empty_elements_are_ignored() {
+            assert_eq!(ranges("text/html,"), vec![range("text", "html", 1.0)]);
+            assert_eq!(ranges("text/html, ,text/plain"), vec![
+                range("text", "html", 1.0),
+                range("text", "plain", 1.0)
+            ]);
+        }
+
+        #[test]
+        fn whitespace_only_input() {
+            assert_eq!(ranges(""), vec![]);
+            assert_eq!(ranges(" \t "), vec![]);
+        }
+
+        #[test]
+        fn invalid_element() {
+            let err = parse_accept("text/html, text/plain;q=2").unwrap_err();
+            assert_eq!(err, AcceptError { index: 1, error: ParseError::InvalidQuality });
+            let err = parse_accept("text/html; x=\"a,b").unwrap_err();
+            assert_eq!(err.index, 0);
+        }
+    }
+
+    mod best_match {
+        use super::super::{best_match, parse_accept, MediaType};
+
+        fn available(types: &[&str]) -> Vec<MediaType> {
+            types.iter().map(|ty| MediaType::parse(ty).unwrap()).collect()
+        }
+
+        fn best<'a>(available: &'a [MediaType], accept: &str) -> Option<&'a str> {
+            let accept = parse_accept(accept).unwrap();
+            best_match(available, &accept).map(|media_type| media_type.subtype())
+        }
+
+        #[test]
+        fn prefers_html() {
+            let offered = available(&["application/json", "text/html"]);
+            let accept = "text/html, application/xhtml+xml, application/xml;q=0.9, */*;q=0.8";
+            assert_eq!(best(&offered, accept), Some("html"));
+        }
+
+        #[test]
+        fn falls_back_to_wildcard() {
+            let offered = available(&["application/json", "text/html"]);
+            assert_eq!(best(&offered, "image/png, */*;q=0.1"), Some("json"));
+        }
+
+        #[test]
+        fn ties_are_broken_by_available_order() {
+            let offered = available(&["application/json", "text/html"]);
+            assert_eq!(best(&offered, "*/*"), Some("json"));
+            let offered = available(&["text/html", "application/json"]);
+            assert_eq!(best(&offered, "*/*"), Some("html"));
+        }
+
+        #[test]
+        fn zero_quality_is_excluded() {
+            let offered = available(&["application/json", "text/html"]);
+            assert_eq!(best(&offered, "application/json;q=0, text/*;q=0.5"), Some("html"));
+            assert_eq!(best(&offered, "application/json;q=0, */*;q=0.5"), Some("html"));
+            assert_eq!(best(&offered, "application/json;q=0"), None);
+        }
+
+        #[test]
+        fn no_match() {
+            let offered = available(&["application/json"]);
+            assert_eq!(best(&offered, "text/*"), None);
+            assert_eq!(best(&offered, ""), None);
+        }
+    }
+
+    mod parse_media_type {
+        use super::super::{parse_media_type, MediaTypeError};
+
+        #[test]
+        fn parses_media_type() {
+            let mt = parse_media_type("text/plain; charset=utf-8").unwrap();
+            assert_eq!(mt.ty(), "text");
+            assert_eq!(mt.subtype(), "plain");
+            let params = mt.params().collect::<Vec<_>>();
+            assert_eq!(params, vec![("charset", "utf-8")]);
+        }
+
+        #[test]
+        fn error_tells_failed_segment() {
+            let err = parse_media_type("text/plain; charset").unwrap_err();
+            assert_eq!(err, MediaTypeError::MissingParamValue);
+            let err = parse_media_type("text/pl ain").unwrap_err();
+            assert_eq!(err, MediaTypeError::ExpectedSemicolon);
+        }
+    }
+
+    mod parse_quoted_with {
+        use qs::error::CoreError;
+        use quoted_string::{MimeGrammar, OffsetError};
+        use quoted_string::MimeGrammar::*;
+        use super::super::{parse_quoted_with, ParseError};
+
+        const ALL: &[MimeGrammar] = &[Modern, ModernUtf8, Obs, ObsUtf8];
+
+        fn invalid(err: CoreError, offset: usize) -> OffsetError<ParseError> {
+            OffsetError::new(ParseError::InvalidParamValue(err), offset)
+        }
+
+        #[test]
+        fn common_input_is_accepted_by_all() {
+            for grammar in ALL {
+                assert_eq!(parse_quoted_with(*grammar, r#""a\"b""#).unwrap(), r#"a"b"#);
+                assert_eq!(parse_quoted_with(*grammar, "token").unwrap(), "token");
+            }
+        }
+
+        #[test]
+        fn utf8_is_only_accepted_by_utf8_grammars() {
+            for grammar in ALL {
+                let res = parse_quoted_with(*grammar, "\"aä\"");
+                match *grammar {
+                    ModernUtf8 | ObsUtf8 => assert_eq!(res.unwrap(), "aä"),
+                    Modern | Obs => assert_eq!(res, Err(invalid(CoreError::InvalidChar, 2)))
+                }
+            }
+        }
+
+        #[test]
+        fn quoted_ctls_are_only_accepted_by_obs_grammars() {
+            for grammar in ALL {
+                let res = parse_quoted_with(*grammar, "\"a\\\x01\"");
+                match *grammar {
+                    Obs | ObsUtf8 => assert_eq!(res.unwrap(), "a\x01"),
+                    Modern | ModernUtf8 => assert_eq!(res, Err(invalid(CoreError::UnquoteableCharQuoted, 3)))
+                }
+            }
+        }
+
+        #[test]
+        fn unterminated_input() {
+            let res = parse_quoted_with(Modern, "\"abc");
+            assert_eq!(res, Err(invalid(CoreError::DoesNotEndWithDQuotes, 0)));
+        }
+    }
+
+    mod validate_boundary {
+        use super::super::{validate_boundary, ParseError};
+
+        #[test]
+        fn valid() {
+            assert_eq!(validate_boundary("simple boundary"), Ok(()));
+            assert_eq!(validate_boundary("=_'()+_,-./:=?09azAZ"), Ok(()));
+            assert_eq!(validate_boundary(&"a".repeat(70)), Ok(()));
+        }
+
+        #[test]
+        fn ending_in_space() {
+            assert_eq!(validate_boundary("boundary "), Err(ParseError::InvalidBoundary));
+        }
+
+        #[test]
+        fn over_70_chars() {
+            assert_eq!(validate_boundary(&"a".repeat(71)), Err(ParseError::InvalidBoundary));
+        }
+
+        #[test]
+        fn invalid_chars() {
+            assert_eq!(validate_boundary(""), Err(ParseError::InvalidBoundary));
+            assert_eq!(validate_boundary("a;b"), Err(ParseError::InvalidBoundary));
+            assert_eq!(validate_boundary("a\tb"), Err(ParseError::InvalidBoundary));
+            assert_eq!(validate_boundary("ä"), Err(ParseError::InvalidBoundary));
+        }
+    }
+
+    mod collect_params {
+        use ext_value::ContinuationError;
+        use super::super::{collect_params, ParseError};
+
+        fn owned(params: &[(&str, &str)]) -> Vec<(String, String)> {
+            params.iter().map(|&(name, value)| (name.to_owned(), value.to_owned())).collect()
+        }
+
+        #[test]
+        fn out_of_order_sections_are_merged() {
+            let res = collect_params("; name*1=\"world.txt\"; charset=utf-8; name*0=\"hello \"");
+            assert_eq!(res.unwrap(), owned(&[("name", "hello world.txt"), ("charset", "utf-8")]));
+        }
+
+        #[test]
+        fn extended_sections_are_decoded() {
+            let res = collect_params("; filename*0*=utf-8''%C2%A3; filename*1*=%20.txt");
+            assert_eq!(res.unwrap(), owned(&[("filename", "£ .txt")]));
+        }
+
+        #[test]
+        fn missing_index_is_rejected() {
+            let res = collect_params("; name*0=a; name*2=c");
+            let expected = ContinuationError::MissingIndex { name: "name".to_owned(), index: 1 };
+            assert_eq!(res, Err(ParseError::InvalidContinuation(expected)));
+        }
+
+        #[test]
+        fn duplicate_index_is_rejected() {
+            let res = collect_params("; name*0=a; name*0=b");
+            let expected = ContinuationError::DuplicateIndex { name: "name".to_owned(), index: 0 };
+            assert_eq!(res, Err(ParseError::InvalidContinuation(expected)));
+        }
+    }
+
+    mod Params {
+        use std::borrow::Cow;
+        use qs::error::CoreError;
+        use quoted_string::MimeGrammar;
+        use super::super::{Params, ParseError};
+
+        #[test]
+        fn unquoted_value_is_borrowed() {
+            let mut params = Params::new("; charset=\"utf-8\"", MimeGrammar::Modern);
+            assert!(matches!(params.next(), Some(Ok(("charset", Cow::Borrowed("utf-8"))))));
+            assert_eq!(params.next(), None);
+        }
+
+        #[test]
+        fn quoted_pairs_are_resolved() {
+            let mut params = Params::new(r#";name="a\"b" ; x = y"#, MimeGrammar::Modern);
+            match params.next() {
+                Some(Ok(("name", Cow::Owned(value)))) => assert_eq!(value, r#"a"b"#),
+                other => panic!("unexpected {:?}", other)
+            }
+            assert!(matches!(params.next(), Some(Ok(("x", Cow::Borrowed("y"))))));
+            assert_eq!(params.next(), None);
+        }
+
+        #[test]
+        fn error_is_fused() {
+            let mut params = Params::new("; a=b; c=\"d; e=f", MimeGrammar::Modern);
+            assert!(matches!(params.next(), Some(Ok(("a", Cow::Borrowed("b"))))));
+            let err = ParseError::InvalidParamValue(CoreError::DoesNotEndWithDQuotes);
+            assert_eq!(params.next(), Some(Err(err)));
+            assert_eq!(params.next(), None);
+            assert_eq!(params.next(), None);
+        }
+    }
+}
@@ -1,6 +1,9 @@
+use std::error::Error;
+use std::fmt::{self, Display};
+
 use penc::EncodeSet;
 
-use lookup_tables::{MediaTypeChars, Token, HttpToken};
+use lookup_tables::{MediaTypeChars, Token, HttpToken, AttributeChar};
 use lut::Table;
 
 /// a percent-encoding EncodeSet for Http tokens
@@ -29,12 +32,153 @@ impl EncodeSet for MimePercentEncodeSet {
     }
 }
 
+/// a percent-encoding EncodeSet for rfc2231 attribute-chars
+///
+/// (usable for the value part of rfc2231 extended values, unlike `MimePercentEncodeSet`
+/// this also encodes `'*'`, `'\''` and `'%'`)
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct AttributeCharPercentEncodeSet;
+
+impl EncodeSet for AttributeCharPercentEncodeSet {
+    fn contains(&self, byte: u8) -> bool {
+        //true == it needs encoding
+        !MediaTypeChars::check_at(byte as usize, AttributeChar)
+    }
+}
+
+/// error returned by `percent_decode`
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum PercentError {
+    /// a `'%'` was followed by less than two chars (e.g. `"a%2"` or `"a%"`)
+    Incomplete,
+    /// a `'%'` was followed by a char which is not a hex digit (e.g. `"%GG"`)
+    NonHexDigit
+}
+
+impl Display for PercentError {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        fter.write_str(match *self {
+            PercentError::Incomplete => "'%' at the end of the input is not followed by two hex digits",
+            PercentError::NonHexDigit => "'%' is followed by a char which is not a hex digit"
+        })
+    }
+}
+
+impl Error for PercentError {}
+
+/// percent encodes all bytes which are not attribute-chars and appends them to `out`
+///
+/// This is the encoding used by the value part of RFC 2231 and RFC 5987 (RFC 8187)
+/// extended values. Upper case hex digits are used.
+pub fn percent_encode(bytes: &[u8], out: &mut String) {
+    out.extend(penc::percent_encode(bytes, AttributeCharPercentEncodeSet));
+}
+
+/// decodes all `%HH` sequences in the input, other chars are kept as they are
+///
+/// Unlike the lenient decoding of the `percent_encoding` crate a `'%'` not followed
+/// by two hex digits is a error.
+pub fn percent_decode(input: &str) -> Result<Vec<u8>, PercentError> {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let bch = bytes[idx];
+        if bch == b'%' {
+            let hh = bytes.get(idx+1..idx+3).ok_or(PercentError::Incomplete)?;
+            let high = hex_value(hh[0]).ok_or(PercentError::NonHexDigit)?;
+            let low = hex_value(hh[1]).ok_or(PercentError::NonHexDigit)?;
+            out.push(high << 4 | low);
+            idx += 3;
+        } else {
+            out.push(bch);
+            idx += 1;
+        }
+    }
+    Ok(out)
+}
+
+fn hex_value(bch: u8) -> Option<u8> {
+    (bch as char).to_digit(16).map(|digit| digit as u8)
+}
+
 #[cfg(test)]
 mod test {
     use std::borrow::Cow;
     use penc::percent_encode;
     use super::{HttpPercentEncodeSet, MimePercentEncodeSet};
 
+    mod percent_encode {
+        use super::super::percent_encode;
+
+        fn encode(bytes: &[u8]) -> String {
+            let mut out = String::new();
+            percent_encode(bytes, &mut out);
+            out
+        }
+
+        #[test]
+        fn attribute_chars_are_not_encoded() {
+            let safe = "abcXYZ019!#$&+-.^_`|~";
+            assert_eq!(encode(safe.as_bytes()), safe);
+        }
+
+        #[test]
+        fn other_bytes_are_encoded_with_upper_case_hex() {
+            assert_eq!(encode(b"a b*'%\xff\x00"), "a%20b%2A%27%25%FF%00");
+            assert_eq!(encode("ä".as_bytes()), "%C3%A4");
+        }
+
+        #[test]
+        fn appends_to_output() {
+            let mut out = "utf-8''".to_owned();
+            percent_encode(b"a;", &mut out);
+            assert_eq!(out, "utf-8''a%3B");
+        }
+    }
+
+    mod percent_decode {
+        use super::super::{percent_decode, percent_encode, PercentError};
+
+        #[test]
+        fn decodes_hex_sequences() {
+            assert_eq!(percent_decode("a%20b%c3%A4"), Ok(b"a b\xc3\xa4".to_vec()));
+            assert_eq!(percent_decode(""), Ok(vec![]));
+            assert_eq!(percent_decode("%C2%A3"), Ok("£".as_bytes().to_vec()));
+        }
+
+        #[test]
+        fn other_chars_are_passed_through() {
+            assert_eq!(percent_decode("a+b*ä"), Ok("a+b*ä".as_bytes().to_vec()));
+        }
+
+        #[test]
+        fn round_trips() {
+            let inputs: &[&[u8]] = &[b"", b"abc", b"a b%c", b"\x00\xff\x7f", "ä€".as_bytes()];
+            for &input in inputs {
+                let mut encoded = String::new();
+                percent_encode(input, &mut encoded);
+                assert_eq!(percent_decode(&encoded), Ok(input.to_vec()));
+            }
+        }
+
+        #[test]
+        fn incomplete_sequence() {
+            assert_eq!(percent_decode("a%2"), Err(PercentError::Incomplete));
+            assert_eq!(percent_decode("%"), Err(PercentError::Incomplete));
+            assert_eq!(percent_decode("ab%"), Err(PercentError::Incomplete));
+        }
+
+        #[test]
+        fn non_hex_digits() {
+            assert_eq!(percent_decode("%GG"), Err(PercentError::NonHexDigit));
+            assert_eq!(percent_decode("%2G"), Err(PercentError::NonHexDigit));
+            assert_eq!(percent_decode("%+1"), Err(PercentError::NonHexDigit));
+            assert_eq!(percent_decode("%G0"), Err(PercentError::NonHexDigit));
+            assert_eq!(percent_decode("a%%20"), Err(PercentError::NonHexDigit));
+        }
+    }
+
     #[test]
     fn what_to_encode_and_what_not_is_not_switched_around() {
         let input = "a\0b";
@@ -0,0 +1,1303 @@
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::iter::FusedIterator;
+use std::str;
+
+use lut::Table;
+use lookup_tables::{MediaTypeChars, Token};
+use qs::error::CoreError;
+#[cfg(feature = "debug-trace")]
+use qs::spec::State;
+use qs::spec::{
+    PartialCodePoint,
+    ParsingImpl,
+    ScanAutomaton,
+};
+
+use super::pcp_from_byte;
+
+/// decodes a quoted-string wrt. the given `ParsingImpl`
+///
+/// This strips the surrounding `'"'`, resolves quoted-pairs and removes
+/// everything the `ParsingImpl` does not emit (e.g. the `"\r\n"` of a FWS
+/// in the mime grammars).
+///
+/// If the input does not start with `'"'` it is treated as a bare token
+/// and returned as is, as long as it only contains (mime) token chars.
+///
+/// # Error
+///
+/// - any error the `ParsingImpl` returns
+/// - `CoreError::QuotedStringAlreadyEnded` if there is any input after the
+///   closing `'"'`
+/// - `CoreError::InvalidChar` if it's treated as a bare token but contains
+///   non token chars
+/// - `CoreError::ZeroSizedValue` if the input is empty
+pub fn unquote<P: ParsingImpl>(input: &str) -> Result<String, CoreError> {
+    unquote_cow::<P>(input).map(Cow::into_owned)
+}
+
+/// like `unquote` but only allocates if the content differs from the input
+///
+/// If the quoted-string neither contains quoted-pairs nor anything else which
+/// is not emitted by the `ParsingImpl` (e.g. a FWS) the part between the
+/// surrounding `'"'` is returned as `Cow::Borrowed`. Bare tokens are always
+/// borrowed.
+pub fn unquote_cow<'a, P: ParsingImpl>(input: &'a str) -> Result<Cow<'a, str>, CoreError> {
+    if !input.starts_with('"') {
+        return validate_token(input.as_bytes()).map(|_| Cow::Borrowed(input));
+    }
+
+    let bytes = input.as_bytes();
+    // only allocated once the first byte is not emitted
+    let mut buffer: Option<Vec<u8>> = None;
+    drive_automaton_to_end::<P, CoreError, _>(bytes, PartialCodePoint::from_utf8_byte, |idx, bch, emit| {
+        match buffer {
+            Some(ref mut buffer) if emit => buffer.push(bch),
+            Some(_) => {},
+            None if !emit => {
+                let mut new_buffer = Vec::with_capacity(bytes.len());
+                new_buffer.extend_from_slice(&bytes[1..idx]);
+                buffer = Some(new_buffer);
+            },
+            None => {}
+        }
+        Ok(())
+    }).map_err(|err| err.kind)?;
+
+    Ok(match buffer {
+        None => Cow::Borrowed(&input[1..input.len() - 1]),
+        Some(buffer) => Cow::Owned(utf8_from_emitted(buffer))
+    })
+}
+
+/// returns the index just past the closing `'"'` of the quoted-string the input starts with
+///
+/// Unlike `unquote` any input after the quoted-string is ignored, which makes it
+/// possible to continue parsing e.g. the next parameter of a header.
+///
+/// # Error
+///
+/// - `CoreError::DoesNotStartWithDQuotes` if the input does not start with `'"'`
+/// - `CoreError::DoesNotEndWithDQuotes` if the quoted-string is not closed
+/// - any other error the `ParsingImpl` returns
+pub fn quoted_string_end<P: ParsingImpl>(input: &str) -> Result<usize, CoreError> {
+    quoted_string_end_with_offset::<P>(input).map_err(|err| err.kind)
+}
+
+/// like `quoted_string_end` but reports the byte offset at which scanning failed
+///
+/// The offset is the same as the one `unquote_with_offset` reports.
+pub fn quoted_string_end_with_offset<P: ParsingImpl>(input: &str)
+    -> Result<usize, OffsetError<CoreError>>
+{
+    if !input.starts_with('"') {
+        return Err(OffsetError::new(CoreError::DoesNotStartWithDQuotes, 0));
+    }
+    drive_automaton::<P, CoreError, _>(input.as_bytes(), PartialCodePoint::from_utf8_byte, |_, _, _| Ok(()))
+        .map_err(|err| OffsetError::new(err.kind, char_start(input, err.offset)))
+}
+
+/// like `unquote` but appends the output to the given buffer
+///
+/// The buffer is not cleared, so it can be used to build up composite output.
+/// If a error is returned the buffer is unchanged.
+pub fn unquote_into<P: ParsingImpl>(input: &str, out: &mut String) -> Result<(), CoreError> {
+    let content = unquote_cow::<P>(input)?;
+    out.push_str(&content);
+    Ok(())
+}
+
+/// like `unquote_cow` but the input has to be a quoted-string
+///
+/// # Error
+///
+/// returns `CoreError::DoesNotStartWithDQuotes` if the input does not start with
+/// `'"'`, else wise the same errors as `unquote_cow`
+pub fn unquote_quoted<'a, P: ParsingImpl>(input: &'a str) -> Result<Cow<'a, str>, CoreError> {
+    if input.starts_with('"') {
+        unquote_cow::<P>(input)
+    } else {
+        Err(CoreError::DoesNotStartWithDQuotes)
+    }
+}
+
+/// like `unquote` but operates directly on bytes
+///
+/// This avoids validating the whole input as utf-8 up front. Non us-ascii bytes are
+/// only accepted if the `ParsingImpl` accepts them (e.g. `MimeParsingUtf8`) and have
+/// to form well-formed utf-8 sequences, so e.g. latin-1 encoded http `obs-text`
+/// or overlong and truncated sequences are rejected.
+///
+/// # Error
+///
+/// - `UnquoteBytesError::Core` with the same errors as `unquote`
+/// - `UnquoteBytesError::InvalidUtf8` if the non us-ascii bytes are not valid utf-8
+pub fn unquote_bytes<P: ParsingImpl>(input: &[u8]) -> Result<Vec<u8>, UnquoteBytesError> {
+    if input.first() != Some(&b'"') {
+        validate_token(input)?;
+        return Ok(input.to_vec());
+    }
+
+    let mut out = Vec::with_capacity(input.len());
+    drive_automaton_to_end::<P, UnquoteBytesError, _>(input, pcp_from_byte, |_, bch, emit| {
+        if emit {
+            out.push(bch);
+        }
+        Ok(())
+    }).map_err(|err| err.kind)?;
+    validate_utf8(&out)?;
+    Ok(out)
+}
+
+/// the default limit of folds (FWS) in a single quoted-string, see `unquote_max_folds`
+pub const DEFAULT_MAX_FOLDS: usize = 50;
+
+/// like `unquote` but aborts once the quoted-string contains more than `max_folds` FWS
+///
+/// A header value with a huge number of consecutive folds (`"\r\n "`) can be used
+/// to blow up parsing time. Each `"\r\n"` consumed as part of a FWS counts as one
+/// fold, a quoted `'\n'` (in the obs grammar) doesn't. `DEFAULT_MAX_FOLDS` is a
+/// sensible limit for most use cases. Parsing stops as soon as the limit is exceeded.
+///
+/// # Error
+///
+/// - `UnquoteLimitError::ExceedsMaxFolds` if there are more than `max_folds` folds
+/// - `UnquoteLimitError::Core` with any error `unquote` would return
+pub fn unquote_max_folds<P: ParsingImpl>(input: &str, max_folds: usize)
+    -> Result<String, UnquoteLimitError>
+{
+    let bytes = input.as_bytes();
+    if bytes.first() != Some(&b'"') {
+        validate_token(bytes)?;
+        return Ok(input.to_owned());
+    }
+
+    let mut buffer = Vec::with_capacity(bytes.len());
+    let mut folds = 0;
+    drive_automaton_to_end::<P, UnquoteLimitError, _>(bytes, PartialCodePoint::from_utf8_byte, |_, bch, emit| {
+        if emit {
+            buffer.push(bch);
+        } else if bch == b'\n' {
+            folds += 1;
+            if folds > max_folds {
+                return Err(UnquoteLimitError::ExceedsMaxFolds);
+            }
+        }
+        Ok(())
+    }).map_err(|err| err.kind)?;
+    Ok(utf8_from_emitted(buffer))
+}
+
+/// error returned by `unquote_bytes`
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum UnquoteBytesError {
+    /// unquoting failed
+    Core(CoreError),
+    /// the decoded bytes are not valid utf-8
+    InvalidUtf8
+}
+
+impl Display for UnquoteBytesError {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            UnquoteBytesError::Core(ref err) => Display::fmt(err, fter),
+            UnquoteBytesError::InvalidUtf8 =>
+                fter.write_str("decoded quoted-string is not valid utf-8")
+        }
+    }
+}
+
+impl Error for UnquoteBytesError {}
+
+impl From<CoreError> for UnquoteBytesError {
+    fn from(err: CoreError) -> Self {
+        UnquoteBytesError::Core(err)
+    }
+}
+
+/// error returned by `unquote_limited` and `unquote_max_folds`
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum UnquoteLimitError {
+    /// unquoting failed
+    Core(CoreError),
+    /// the decoded output would be longer than the limit (only from `unquote_limited`)
+    ExceedsMaxLen,
+    /// the quoted-string contains more FWS than allowed (only from `unquote_max_folds`)
+    ExceedsMaxFolds
+}
+
+impl Display for UnquoteLimitError {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            UnquoteLimitError::Core(ref err) => Display::fmt(err, fter),
+            UnquoteLimitError::ExceedsMaxLen =>
+                fter.write_str("decoded quoted-string exceeds the maximal length"),
+            UnquoteLimitError::ExceedsMaxFolds =>
+                fter.write_str("quoted-string contains more folds than allowed")
+        }
+    }
+}
+
+impl Error for UnquoteLimitError {}
+
+impl From<CoreError> for UnquoteLimitError {
+    fn from(err: CoreError) -> Self {
+        UnquoteLimitError::Core(err)
+    }
+}
+
+/// like `unquote` but aborts once the decoded output exceeds `max_len` bytes
+///
+/// The decoded bytes are counted (not the input bytes), so the check also covers
+/// quoted-pairs and FWS. Parsing stops as soon as the limit is exceeded, i.e. the
+/// rest of the input is not looked at.
+///
+/// # Error
+///
+/// - `UnquoteLimitError::ExceedsMaxLen` if the decoded output is longer than `max_len`
+/// - `UnquoteLimitError::Core` with any error `unquote` would return
+pub fn unquote_limited<P: ParsingImpl>(input: &str, max_len: usize)
+    -> Result<String, UnquoteLimitError>
+{
+    let bytes = input.as_bytes();
+    if bytes.first() != Some(&b'"') {
+        if bytes.len() > max_len {
+            return Err(UnquoteLimitError::ExceedsMaxLen);
+        }
+        validate_token(bytes)?;
+        return Ok(input.to_owned());
+    }
+
+    let mut buffer = Vec::new();
+    drive_automaton_to_end::<P, UnquoteLimitError, _>(bytes, PartialCodePoint::from_utf8_byte, |_, bch, emit| {
+        if emit {
+            if buffer.len() >= max_len {
+                return Err(UnquoteLimitError::ExceedsMaxLen);
+            }
+            buffer.push(bch);
+        }
+        Ok(())
+    }).map_err(|err| err.kind)?;
+    Ok(utf8_from_emitted(buffer))
+}
+
+/// a error together with the byte offset in the input at which it occurred
+///
+/// The offset is the index of the first byte of the offending code point. If
+/// the input ended before the quoted-string was closed it's the index of the
+/// opening `'"'`, so that the unterminated quoted-string can be pointed at.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct OffsetError<K> {
+    /// the error which occurred
+    pub kind: K,
+    /// the byte offset of the offending code point
+    pub offset: usize
+}
+
+impl<K> OffsetError<K> {
+    /// creates a new `OffsetError`
+    pub fn new(kind: K, offset: usize) -> Self {
+        OffsetError { kind, offset }
+    }
+}
+
+impl<K: Display> Display for OffsetError<K> {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        write!(fter, "{} at byte {}", self.kind, self.offset)
+    }
+}
+
+impl<K: Error> Error for OffsetError<K> {}
+
+/// like `unquote` but reports the byte offset at which unquoting failed
+///
+/// # Error
+///
+/// the same errors as `unquote`, wrapped in a `OffsetError`
+pub fn unquote_with_offset<P: ParsingImpl>(input: &str) -> Result<String, OffsetError<CoreError>> {
+    let bytes = input.as_bytes();
+    if bytes.first() != Some(&b'"') {
+        if bytes.is_empty() {
+            return Err(OffsetError::new(CoreError::ZeroSizedValue, 0));
+        }
+        // non us-ascii code points are no token chars, so this is always the first byte
+        return match bytes.iter().position(|bch| !MediaTypeChars::check_at(*bch as usize, Token)) {
+            Some(idx) => Err(OffsetError::new(CoreError::InvalidChar, idx)),
+            None => Ok(input.to_owned())
+        };
+    }
+
+    let mut buffer = Vec::with_capacity(bytes.len());
+    drive_automaton_to_end::<P, CoreError, _>(bytes, PartialCodePoint::from_utf8_byte, |_, bch, emit| {
+        if emit {
+            buffer.push(bch);
+        }
+        Ok(())
+    }).map_err(|err| OffsetError::new(err.kind, char_start(input, err.offset)))?;
+    Ok(utf8_from_emitted(buffer))
+}
+
+/// drives a `ScanAutomaton` over the quoted-string the input starts with
+///
+/// `on_byte` is called with the index, the byte and whether it was emitted for
+/// every byte between the surrounding `'"'`. A error returned by it aborts the
+/// scan. On success the index just past the closing `'"'` is returned, input
+/// after it is not looked at.
+///
+/// Errors carry the index of the offending byte (not code point), or 0 (the
+/// opening `'"'`) if the input ended before the quoted-string was closed.
+fn drive_automaton<P, E, F>(input: &[u8], to_pcp: fn(u8) -> PartialCodePoint, mut on_byte: F)
+    -> Result<usize, OffsetError<E>>
+    where P: ParsingImpl, E: From<CoreError>, F: FnMut(usize, u8, bool) -> Result<(), E>
+{
+    let mut automaton = ScanAutomaton::<P>::new();
+    for (idx, &bch) in input.iter().enumerate() {
+        let emit = automaton.advance(to_pcp(bch))
+            .map_err(|err| OffsetError::new(E::from(err), idx))?;
+        if automaton.did_end() {
+            return Ok(idx + 1);
+        }
+        // the opening '"' is never emitted
+        if idx > 0 {
+            on_byte(idx, bch, emit).map_err(|err| OffsetError::new(err, idx))?;
+        }
+    }
+    automaton.end().map_err(|err| OffsetError::new(E::from(err), 0))?;
+    unreachable!("[BUG] automaton accepted end of input without ending the quoted-string")
+}
+
+/// like `drive_automaton` but the quoted-string has to span the whole input
+///
+/// Input after the closing `'"'` is a `CoreError::QuotedStringAlreadyEnded`.
+fn drive_automaton_to_end<P, E, F>(input: &[u8], to_pcp: fn(u8) -> PartialCodePoint, on_byte: F)
+    -> Result<(), OffsetError<E>>
+    where P: ParsingImpl, E: From<CoreError>, F: FnMut(usize, u8, bool) -> Result<(), E>
+{
+    let end = drive_automaton::<P, E, F>(input, to_pcp, on_byte)?;
+    if end == input.len() {
+        Ok(())
+    } else {
+        Err(OffsetError::new(E::from(CoreError::QuotedStringAlreadyEnded), end))
+    }
+}
+
+fn utf8_from_emitted(buffer: Vec<u8>) -> String {
+    String::from_utf8(buffer)
+        .expect("[BUG] automaton caused a code point to be only partially emitted")
+}
+
+/// the index of the first byte of the code point the byte at `idx` belongs to
+fn char_start(input: &str, idx: usize) -> usize {
+    (0..=idx).rev()
+        .find(|idx| input.is_char_boundary(*idx))
+        .unwrap_or(0)
+}
+
+/// error returned by `UnquoteIter`
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum UnquoteError {
+    /// the content ends with a `'\\'` which does not escape anything
+    DanglingEscape,
+    /// the content contains a `'"'` which is not escaped
+    UnescapedDQuote
+}
+
+impl Display for UnquoteError {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            UnquoteError::DanglingEscape =>
+                fter.write_str("quoted-string content ends with a dangling '\\'"),
+            UnquoteError::UnescapedDQuote =>
+                fter.write_str("quoted-string content contains a unescaped '\"'")
+        }
+    }
+}
+
+impl Error for UnquoteError {}
+
+/// a iterator over the decoded bytes of the content of a quoted-string
+///
+/// The input is the part between the surrounding `'"'` (which are not included).
+/// Quoted-pairs are resolved one at a time without allocating, so this can be
+/// used to stream large values. Only quoted-pairs are handled, the content is
+/// not validated wrt. a grammar (e.g. FWS are not removed). After the first error
+/// the iterator is fused.
+#[derive(Debug, Clone)]
+pub struct UnquoteIter<'a> {
+    content: &'a [u8],
+    failed: bool
+}
+
+impl<'a> UnquoteIter<'a> {
+    /// creates a iterator over the decoded content (without the surrounding `'"'`)
+    pub fn new(content: &'a str) -> Self {
+        UnquoteIter { content: content.as_bytes(), failed: false }
+    }
+
+    fn fail(&mut self, err: UnquoteError) -> Option<Result<u8, UnquoteError>> {
+        self.failed = true;
+        Some(Err(err))
+    }
+}
+
+impl<'a> Iterator for UnquoteIter<'a> {
+    type Item = Result<u8, UnquoteError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let (&first, rest) = self.content.split_first()?;
+        self.content = rest;
+        match first {
+            b'\\' => match self.content.split_first() {
+                Some((&escaped, rest)) => {
+                    self.content = rest;
+                    Some(Ok(escaped))
+                },
+                None => self.fail(UnquoteError::DanglingEscape)
+            },
+            b'"' => self.fail(UnquoteError::UnescapedDQuote),
+            bch => Some(Ok(bch))
+        }
+    }
+}
+
+impl<'a> FusedIterator for UnquoteIter<'a> {}
+
+/// error returned by `unescape_in_place`
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum UnescapeError {
+    /// the input is not surrounded by `'"'`
+    MissingDQuotes,
+    /// the content between the `'"'` is malformed
+    Content(UnquoteError)
+}
+
+impl Display for UnescapeError {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            UnescapeError::MissingDQuotes =>
+                fter.write_str("quoted-string is not surrounded by '\"'"),
+            UnescapeError::Content(ref err) => Display::fmt(err, fter)
+        }
+    }
+}
+
+impl Error for UnescapeError {}
+
+impl From<UnquoteError> for UnescapeError {
+    fn from(err: UnquoteError) -> Self {
+        UnescapeError::Content(err)
+    }
+}
+
+/// unquotes a quoted-string in place, reusing the buffers allocation
+///
+/// The surrounding `'"'` are removed and quoted-pairs are resolved the same way
+/// `UnquoteIter` does it, afterwards the buffer is truncated to the decoded length.
+/// As with `UnquoteIter` the content is not validated wrt. a grammar.
+///
+/// # Error
+///
+/// The input is validated before it's modified, so on error the buffer is unchanged.
+pub fn unescape_in_place(buf: &mut Vec<u8>) -> Result<(), UnescapeError> {
+    if buf.len() < 2 || buf[0] != b'"' || buf[buf.len() - 1] != b'"' {
+        return Err(UnescapeError::MissingDQuotes);
+    }
+    let end = buf.len() - 1;
+    let mut escaped = false;
+    for &bch in buf[1..end].iter() {
+        match bch {
+            _ if escaped => escaped = false,
+            b'\\' => escaped = true,
+            b'"' => return Err(UnquoteError::UnescapedDQuote.into()),
+            _ => {}
+        }
+    }
+    if escaped {
+        return Err(UnquoteError::DanglingEscape.into());
+    }
+
+    let mut write = 0;
+    let mut read = 1;
+    while read < end {
+        if buf[read] == b'\\' {
+            read += 1;
+        }
+        buf[write] = buf[read];
+        write += 1;
+        read += 1;
+    }
+    buf.truncate(write);
+    Ok(())
+}
+
+/// returns a iterator over the decoded chars of a quoted-string wrt. the given `ParsingImpl`
+///
+/// This decodes the same way `unquote` does, but on the fly: quoted-pairs are
+/// resolved and everything the `ParsingImpl` does not emit (e.g. the `"\r\n"`
+/// of a FWS) is skipped without allocating a buffer. Errors are yielded at the
+/// point they are encountered, after which the iterator is fused.
+pub fn unquote_chars<'a, P: ParsingImpl>(input: &'a str) -> UnquoteChars<'a, P> {
+    UnquoteChars {
+        chars: input.chars(),
+        automaton: if input.starts_with('"') { Some(ScanAutomaton::new()) } else { None },
+        pending: if input.is_empty() { Some(CoreError::ZeroSizedValue) } else { None },
+        done: false
+    }
+}
+
+/// iterator returned by `unquote_chars`
+#[derive(Debug, Clone)]
+pub struct UnquoteChars<'a, P: ParsingImpl> {
+    chars: str::Chars<'a>,
+    /// `None` if the input is treated as a bare token
+    automaton: Option<ScanAutomaton<P>>,
+    pending: Option<CoreError>,
+    done: bool
+}
+
+impl<'a, P: ParsingImpl> UnquoteChars<'a, P> {
+    fn fail(&mut self, err: CoreError) -> Option<Result<char, CoreError>> {
+        self.done = true;
+        Some(Err(err))
+    }
+}
+
+impl<'a, P: ParsingImpl> Iterator for UnquoteChars<'a, P> {
+    type Item = Result<char, CoreError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        if let Some(err) = self.pending.take() {
+            return self.fail(err);
+        }
+        let automaton = match self.automaton {
+            Some(ref mut automaton) => automaton,
+            None => return match self.chars.next() {
+                Some(ch) if ch.is_ascii() && MediaTypeChars::check_at(ch as usize, Token) =>
+                    Some(Ok(ch)),
+                Some(_) => self.fail(CoreError::InvalidChar),
+                None => {
+                    self.done = true;
+                    None
+                }
+            }
+        };
+
+        let mut res = None;
+        for ch in self.chars.by_ref() {
+            if automaton.did_end() {
+                res = Some(Err(CoreError::QuotedStringAlreadyEnded));
+                break;
+            }
+            let mut buf = [0u8; 4];
+            let mut emit = Ok(false);
+            // all bytes of a multi byte code point are emitted or not emitted together
+            for bch in ch.encode_utf8(&mut buf).bytes() {
+                emit = automaton.advance(PartialCodePoint::from_utf8_byte(bch));
+                if emit.is_err() {
+                    break;
+                }
+            }
+            match emit {
+                Ok(true) => return Some(Ok(ch)),
+                Ok(false) => {},
+                Err(err) => {
+                    res = Some(Err(err));
+                    break;
+                }
+            }
+        }
+        if res.is_none() {
+            res = automaton.end().err().map(Err);
+        }
+        self.done = true;
+        res
+    }
+}
+
+impl<'a, P: ParsingImpl> FusedIterator for UnquoteChars<'a, P> {}
+
+/// the `(offset, state, emit)` entries recorded by `trace_parse`
+#[cfg(feature = "debug-trace")]
+pub type Trace<P> = Vec<(usize, State<P>, bool)>;
+
+/// records the state of the quoted-string automaton after each byte of the input
+///
+/// Each entry is `(offset, state, emit)` for the byte at `offset`, where `state`
+/// is the state after advancing. The input is fed into the same `ScanAutomaton`
+/// the unquote functions use, which decides about `emit` and errors. As it does
+/// not expose it's state the state is tracked alongside it using the same
+/// transitions (`P::handle_normal_state`, `P::can_be_quoted` and `P::advance`).
+///
+/// The error the automaton failed with is returned alongside the trace, the
+/// rejected byte has a last entry (with the state `State::Failed`). If the input
+/// ends before the quoted-string is closed the error is the one of `ScanAutomaton::end`.
+/// This is meant for troubleshooting (custom) grammars and is only available with
+/// the `debug-trace` feature.
+#[cfg(feature = "debug-trace")]
+pub fn trace_parse<P: ParsingImpl>(input: &str) -> (Trace<P>, Option<CoreError>) {
+    let mut trace = Vec::with_capacity(input.len());
+    let mut automaton = ScanAutomaton::<P>::new();
+    let mut state = State::Start;
+    for (idx, bch) in input.bytes().enumerate() {
+        let pcp = PartialCodePoint::from_utf8_byte(bch);
+        match automaton.advance(pcp) {
+            Ok(emit) => {
+                let (next, traced_emit) = trace_step(state, pcp)
+                    .expect("[BUG] traced state diverged from ScanAutomaton");
+                debug_assert_eq!(emit, traced_emit);
+                state = next;
+                trace.push((idx, state, emit));
+            },
+            Err(err) => {
+                trace.push((idx, State::Failed, false));
+                return (trace, Some(err));
+            }
+        }
+    }
+    let err = automaton.end().err();
+    (trace, err)
+}
+
+/// the transitions of `ScanAutomaton::advance`, which are not public
+#[cfg(feature = "debug-trace")]
+fn trace_step<P: ParsingImpl>(state: State<P>, pcp: PartialCodePoint)
+    -> Result<(State<P>, bool), CoreError>
+{
+    match state {
+        State::Start if pcp.as_u8() == b'"' => Ok((State::Normal, false)),
+        State::Start => Err(CoreError::DoesNotStartWithDQuotes),
+        State::Normal => match pcp.as_u8() {
+            b'"' => Ok((State::End, false)),
+            b'\\' => Ok((State::QPStart, false)),
+            _ => P::handle_normal_state(pcp)
+        },
+        State::QPStart if P::can_be_quoted(pcp) => Ok((State::Normal, true)),
+        State::QPStart => Err(CoreError::UnquoteableCharQuoted),
+        State::Custom(inner) => inner.advance(pcp),
+        State::End => Err(CoreError::QuotedStringAlreadyEnded),
+        State::Failed => Err(CoreError::AdvancedFailedAutomaton)
+    }
+}
+
+/// validates that non us-ascii bytes form well-formed utf-8
+fn validate_utf8(bytes: &[u8]) -> Result<(), UnquoteBytesError> {
+    if bytes.is_ascii() || str::from_utf8(bytes).is_ok() {
+        Ok(())
+    } else {
+        Err(UnquoteBytesError::InvalidUtf8)
+    }
+}
+
+fn validate_token(input: &[u8]) -> Result<(), CoreError> {
+    if input.is_empty() {
+        return Err(CoreError::ZeroSizedValue);
+    }
+    if input.iter().all(|bch| MediaTypeChars::check_at(*bch as usize, Token)) {
+        Ok(())
+    } else {
+        Err(CoreError::InvalidChar)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case)]
+
+    mod unquote {
+        use qs::error::CoreError;
+        use super::super::super::{
+            MimeParsing, MimeParsingUtf8,
+            MimeObsParsing, MimeObsParsingUtf8
+        };
+        use super::super::unquote;
+
+        #[test]
+        fn simple() {
+            assert_eq!(unquote::<MimeParsing>("\"simple\"").unwrap(), "simple");
+        }
+
+        #[test]
+        fn escaped_backslash() {
+            assert_eq!(unquote::<MimeParsing>(r#""a\\b""#).unwrap(), r#"a\b"#);
+        }
+
+        #[test]
+        fn escaped_dquotes() {
+            assert_eq!(unquote::<MimeParsing>(r#""a\"b""#).unwrap(), r#"a"b"#);
+        }
+
+        #[test]
+        fn fws_in_obs_grammar() {
+            assert_eq!(unquote::<MimeObsParsing>("\"a\r\n b\"").unwrap(), "a b");
+            assert_eq!(unquote::<MimeObsParsingUtf8>("\"ä\r\n\tb\"").unwrap(), "ä\tb");
+        }
+
+        #[test]
+        fn utf8_only_in_utf8_grammar() {
+            assert_eq!(unquote::<MimeParsingUtf8>("\"ä\"").unwrap(), "ä");
+            assert_eq!(unquote::<MimeParsing>("\"ä\""), Err(CoreError::InvalidChar));
+        }
+
+        #[test]
+        fn bare_token() {
+            assert_eq!(unquote::<MimeParsing>("utf-8").unwrap(), "utf-8");
+        }
+
+        #[test]
+        fn bare_non_token() {
+            assert_eq!(unquote::<MimeParsing>("a b"), Err(CoreError::InvalidChar));
+            assert_eq!(unquote::<MimeParsing>(""), Err(CoreError::ZeroSizedValue));
+        }
+
+        #[test]
+        fn reject_tailing_garbage() {
+            let res = unquote::<MimeParsing>("\"abc\"def");
+            assert_eq!(res, Err(CoreError::QuotedStringAlreadyEnded));
+        }
+
+        #[test]
+        fn reject_missing_closing_dquotes() {
+            let res = unquote::<MimeParsing>("\"abc");
+            assert_eq!(res, Err(CoreError::DoesNotEndWithDQuotes));
+        }
+    }
+
+    mod unquote_cow {
+        use std::borrow::Cow;
+        use qs::error::CoreError;
+        use super::super::super::{MimeParsing, MimeObsParsing};
+        use super::super::unquote_cow;
+
+        fn assert_borrowed(res: Cow<str>, expected: &str) {
+            match res {
+                Cow::Borrowed(value) => assert_eq!(value, expected),
+                Cow::Owned(_) => panic!("unexpected allocation")
+            }
+        }
+
+        fn assert_owned(res: Cow<str>, expected: &str) {
+            match res {
+                Cow::Owned(value) => assert_eq!(value, expected),
+                Cow::Borrowed(_) => panic!("expected a allocated value")
+            }
+        }
+
+        #[test]
+        fn without_quoted_pairs_is_borrowed() {
+            assert_borrowed(unquote_cow::<MimeParsing>("\"plain\"").unwrap(), "plain");
+        }
+
+        #[test]
+        fn empty_quoted_string_is_borrowed() {
+            assert_borrowed(unquote_cow::<MimeParsing>("\"\"").unwrap(), "");
+        }
+
+        #[test]
+        fn bare_token_is_borrowed() {
+            assert_borrowed(unquote_cow::<MimeParsing>("plain").unwrap(), "plain");
+        }
+
+        #[test]
+        fn with_quoted_pair_is_owned() {
+            assert_owned(unquote_cow::<MimeParsing>(r#""a\"b""#).unwrap(), r#"a"b"#);
+        }
+
+        #[test]
+        fn with_fws_is_owned() {
+            assert_owned(unquote_cow::<MimeObsParsing>("\"a\r\n b\"").unwrap(), "a b");
+        }
+
+        #[test]
+        fn reject_tailing_garbage() {
+            let res = unquote_cow::<MimeParsing>("\"abc\"def");
+            assert_eq!(res, Err(CoreError::QuotedStringAlreadyEnded));
+            let res = unquote_cow::<MimeParsing>("\"a\\\\bc\"def");
+            assert_eq!(res, Err(CoreError::QuotedStringAlreadyEnded));
+        }
+
+        #[test]
+        fn reject_missing_closing_dquotes() {
+            let res = unquote_cow::<MimeParsing>("\"abc");
+            assert_eq!(res, Err(CoreError::DoesNotEndWithDQuotes));
+        }
+
+        #[test]
+        fn reject_unterminated_quoted_pair() {
+            let res = unquote_cow::<MimeParsing>("\"abc\\");
+            assert_eq!(res, Err(CoreError::DoesNotEndWithDQuotes));
+        }
+
+        #[test]
+        fn quoted_pairs_depend_on_grammar() {
+            let res = unquote_cow::<MimeParsing>("\"a\\\x01\"");
+            assert_eq!(res, Err(CoreError::UnquoteableCharQuoted));
+            assert_owned(unquote_cow::<MimeObsParsing>("\"a\\\x01\"").unwrap(), "a\x01");
+        }
+    }
+
+    mod quoted_string_end {
+        use qs::error::CoreError;
+        use super::super::super::{MimeParsing, MimeParsingUtf8};
+        use super::super::{quoted_string_end, quoted_string_end_with_offset, OffsetError};
+
+        #[test]
+        fn followed_by_next_param() {
+            let input = r#""a\"b"; next=1"#;
+            let end = quoted_string_end::<MimeParsing>(input).unwrap();
+            assert_eq!(end, 6);
+            assert_eq!(&input[end..], "; next=1");
+        }
+
+        #[test]
+        fn whole_input() {
+            assert_eq!(quoted_string_end::<MimeParsingUtf8>("\"ä\""), Ok(4));
+        }
+
+        #[test]
+        fn errors() {
+            assert_eq!(quoted_string_end::<MimeParsing>("abc"), Err(CoreError::DoesNotStartWithDQuotes));
+            assert_eq!(quoted_string_end::<MimeParsing>("\"abc"), Err(CoreError::DoesNotEndWithDQuotes));
+            assert_eq!(quoted_string_end::<MimeParsing>("\"a\x01\"; x=1"), Err(CoreError::InvalidChar));
+        }
+
+        #[test]
+        fn error_offsets() {
+            let err = quoted_string_end_with_offset::<MimeParsing>("\"ab\x01\"").unwrap_err();
+            assert_eq!(err, OffsetError::new(CoreError::InvalidChar, 3));
+            let err = quoted_string_end_with_offset::<MimeParsing>("\"aä\"").unwrap_err();
+            assert_eq!(err, OffsetError::new(CoreError::InvalidChar, 2));
+            let err = quoted_string_end_with_offset::<MimeParsing>("\"abc").unwrap_err();
+            assert_eq!(err, OffsetError::new(CoreError::DoesNotEndWithDQuotes, 0));
+        }
+    }
+
+    mod UnquoteIter {
+        use super::super::{UnquoteIter, UnquoteError};
+
+        fn collect(content: &str) -> Result<Vec<u8>, UnquoteError> {
+            UnquoteIter::new(content).collect()
+        }
+
+        #[test]
+        fn resolves_quoted_pairs() {
+            assert_eq!(collect(r#"a\"b"#), Ok(b"a\"b".to_vec()));
+            assert_eq!(collect(r"\\\a"), Ok(b"\\a".to_vec()));
+            assert_eq!(collect(""), Ok(vec![]));
+        }
+
+        #[test]
+        fn non_us_ascii_bytes() {
+            assert_eq!(collect("\\ä"), Ok("ä".as_bytes().to_vec()));
+        }
+
+        #[test]
+        fn dangling_escape() {
+            assert_eq!(collect(r"ab\"), Err(UnquoteError::DanglingEscape));
+            let mut iter = UnquoteIter::new(r"a\");
+            assert_eq!(iter.next(), Some(Ok(b'a')));
+            assert_eq!(iter.next(), Some(Err(UnquoteError::DanglingEscape)));
+            assert_eq!(iter.next(), None);
+        }
+
+        #[test]
+        fn unescaped_dquote() {
+            assert_eq!(collect(r#"a"b"#), Err(UnquoteError::UnescapedDQuote));
+        }
+    }
+
+    mod unescape_in_place {
+        use super::super::{unescape_in_place, UnescapeError, UnquoteError};
+
+        #[test]
+        fn resolves_quoted_pairs_in_place() {
+            let mut buf = br#""a\"b\\c""#.to_vec();
+            let capacity = buf.capacity();
+            assert_eq!(unescape_in_place(&mut buf), Ok(()));
+            assert_eq!(buf.len(), 5);
+            assert_eq!(buf, br#"a"b\c"#.to_vec());
+            assert_eq!(buf.capacity(), capacity);
+        }
+
+        #[test]
+        fn empty_quoted_string() {
+            let mut buf = b"\"\"".to_vec();
+            assert_eq!(unescape_in_place(&mut buf), Ok(()));
+            assert!(buf.is_empty());
+        }
+
+        #[test]
+        fn buffer_is_unchanged_on_error() {
+            let inputs: &[(&[u8], UnescapeError)] = &[
+                (b"abc", UnescapeError::MissingDQuotes),
+                (b"\"", UnescapeError::MissingDQuotes),
+                (b"\"abc", UnescapeError::MissingDQuotes),
+                (br#""a\""#, UnescapeError::Content(UnquoteError::DanglingEscape)),
+                (br#""\a"b""#, UnescapeError::Content(UnquoteError::UnescapedDQuote)),
+            ];
+            for &(input, err) in inputs {
+                let mut buf = input.to_vec();
+                assert_eq!(unescape_in_place(&mut buf), Err(err));
+                assert_eq!(buf, input.to_vec());
+            }
+        }
+    }
+
+    mod unquote_chars {
+        use qs::error::CoreError;
+        use super::super::super::{MimeParsing, MimeParsingUtf8, MimeObsParsing};
+        use super::super::{unquote_chars, unquote};
+
+        fn collect<P: ::qs::spec::ParsingImpl>(input: &str) -> Result<String, CoreError> {
+            unquote_chars::<P>(input).collect()
+        }
+
+        #[test]
+        fn same_output_as_unquote() {
+            let inputs = [
+                "\"abc\"", "\"a\\\"b\"", "\"\"", "\"a\r\n b\"", "token",
+                "\"a\\\\\"", "\"ä\\ö\"", "\"a\"b", "\"abc", "", "to ken", "\"a\rb\""
+            ];
+            for input in inputs.iter() {
+                assert_eq!(collect::<MimeParsingUtf8>(input), unquote::<MimeParsingUtf8>(input), "{:?}", input);
+                assert_eq!(collect::<MimeObsParsing>(input), unquote::<MimeObsParsing>(input), "{:?}", input);
+            }
+        }
+
+        #[test]
+        fn error_mid_stream_then_fused() {
+            let mut iter = unquote_chars::<MimeParsing>("\"ab\0cd\"");
+            assert_eq!(iter.next(), Some(Ok('a')));
+            assert_eq!(iter.next(), Some(Ok('b')));
+            assert_eq!(iter.next(), Some(Err(CoreError::InvalidChar)));
+            assert_eq!(iter.next(), None);
+            assert_eq!(iter.next(), None);
+        }
+
+        #[test]
+        fn unterminated() {
+            let mut iter = unquote_chars::<MimeParsing>("\"a");
+            assert_eq!(iter.next(), Some(Ok('a')));
+            assert_eq!(iter.next(), Some(Err(CoreError::DoesNotEndWithDQuotes)));
+            assert_eq!(iter.next(), None);
+        }
+    }
+
+    mod unquote_into {
+        use qs::error::CoreError;
+        use super::super::super::MimeParsing;
+        use super::super::unquote_into;
+
+        #[test]
+        fn appends_to_buffer() {
+            let mut out = String::new();
+            for input in &["a", "\"b c\"", r#""d\"e""#, "\"\""] {
+                out.push('|');
+                unquote_into::<MimeParsing>(input, &mut out).unwrap();
+            }
+            assert_eq!(out, r#"|a|b c|d"e|"#);
+        }
+
+        #[test]
+        fn buffer_is_unchanged_on_error() {
+            let mut out = String::from("x=");
+            let res = unquote_into::<MimeParsing>("\"a\\\"", &mut out);
+            assert_eq!(res, Err(CoreError::DoesNotEndWithDQuotes));
+            assert_eq!(out, "x=");
+        }
+    }
+
+    mod unquote_bytes {
+        use qs::error::CoreError;
+        use super::super::super::{MimeParsing, MimeParsingUtf8, MimeObsParsing, MimeObsParsingUtf8};
+        use super::super::{unquote_bytes, UnquoteBytesError};
+
+        #[test]
+        fn quoted_string() {
+            let res = unquote_bytes::<MimeParsing>(br#""a\"b""#).unwrap();
+            assert_eq!(res, br#"a"b"#.to_vec());
+            let res = unquote_bytes::<MimeObsParsing>(b"\"a\r\n\tb\"").unwrap();
+            assert_eq!(res, b"a\tb".to_vec());
+        }
+
+        #[test]
+        fn bare_token() {
+            assert_eq!(unquote_bytes::<MimeParsing>(b"utf-8").unwrap(), b"utf-8".to_vec());
+            assert_eq!(unquote_bytes::<MimeParsing>(b"a b"), Err(UnquoteBytesError::Core(CoreError::InvalidChar)));
+        }
+
+        #[test]
+        fn utf8_in_utf8_grammar() {
+            let res = unquote_bytes::<MimeParsingUtf8>("\"ä\"".as_bytes()).unwrap();
+            assert_eq!(res, "ä".as_bytes().to_vec());
+            let res = unquote_bytes::<MimeParsing>("\"ä\"".as_bytes());
+            assert_eq!(res, Err(UnquoteBytesError::Core(CoreError::InvalidChar)));
+            let res = unquote_bytes::<MimeObsParsingUtf8>("\"😀\"".as_bytes()).unwrap();
+            assert_eq!(res, "😀".as_bytes().to_vec());
+        }
+
+        #[test]
+        fn invalid_utf8_is_rejected() {
+            let res = unquote_bytes::<MimeParsingUtf8>(b"\"a\xC3\x28\"");
+            assert_eq!(res, Err(UnquoteBytesError::InvalidUtf8));
+            let res = unquote_bytes::<MimeParsingUtf8>(b"\"\xFF\"");
+            assert_eq!(res, Err(UnquoteBytesError::InvalidUtf8));
+        }
+
+        #[test]
+        fn lone_lead_byte() {
+            let res = unquote_bytes::<MimeParsingUtf8>(b"\"\xC0\"");
+            assert_eq!(res, Err(UnquoteBytesError::InvalidUtf8));
+            // overlong encoding of '/'
+            let res = unquote_bytes::<MimeObsParsingUtf8>(b"\"\xC0\xAF\"");
+            assert_eq!(res, Err(UnquoteBytesError::InvalidUtf8));
+        }
+
+        #[test]
+        fn truncated_sequence() {
+            let res = unquote_bytes::<MimeParsingUtf8>(b"\"\xE2\x82\"");
+            assert_eq!(res, Err(UnquoteBytesError::InvalidUtf8));
+            let res = unquote_bytes::<MimeParsingUtf8>(b"\"\xE2\x82a\"");
+            assert_eq!(res, Err(UnquoteBytesError::InvalidUtf8));
+        }
+
+        #[test]
+        fn reject_tailing_garbage() {
+            let res = unquote_bytes::<MimeParsing>(b"\"abc\"def");
+            assert_eq!(res, Err(UnquoteBytesError::Core(CoreError::QuotedStringAlreadyEnded)));
+        }
+    }
+
+    mod unquote_quoted {
+        use std::borrow::Cow;
+        use qs::error::CoreError;
+        use super::super::super::MimeParsing;
+        use super::super::unquote_quoted;
+
+        #[test]
+        fn quoted_string() {
+            let res = unquote_quoted::<MimeParsing>("\"plain\"").unwrap();
+            assert!(matches!(res, Cow::Borrowed("plain")));
+        }
+
+        #[test]
+        fn reject_bare_token() {
+            let res = unquote_quoted::<MimeParsing>("plain");
+            assert_eq!(res, Err(CoreError::DoesNotStartWithDQuotes));
+        }
+    }
+
+    #[cfg(feature = "debug-trace")]
+    mod trace_parse {
+        use qs::error::CoreError;
+        use qs::spec::State;
+        use super::super::super::{MimeParsing, MimeObsParsing, MimeParsingExt, FWSState};
+        use super::super::trace_parse;
+
+        fn custom(state: FWSState) -> State<MimeObsParsing> {
+            MimeObsParsing::custom_state(state, false).0
+        }
+
+        #[test]
+        fn quoted_value_with_escape() {
+            let (trace, err) = trace_parse::<MimeParsing>(r#""a\"b""#);
+            assert_eq!(err, None);
+            assert_eq!(trace, vec![
+                (0, State::Normal, false),
+                (1, State::Normal, true),
+                (2, State::QPStart, false),
+                (3, State::Normal, true),
+                (4, State::Normal, true),
+                (5, State::End, false),
+            ]);
+        }
+
+        #[test]
+        fn stops_at_failure() {
+            let (trace, err) = trace_parse::<MimeParsing>("\"a\0b\"");
+            assert_eq!(err, Some(CoreError::InvalidChar));
+            assert_eq!(trace, vec![
+                (0, State::Normal, false),
+                (1, State::Normal, true),
+                (2, State::Failed, false),
+            ]);
+            let (trace, err) = trace_parse::<MimeParsing>("a");
+            assert_eq!(err, Some(CoreError::DoesNotStartWithDQuotes));
+            assert_eq!(trace, vec![(0, State::Failed, false)]);
+        }
+
+        #[test]
+        fn unterminated_input() {
+            let (trace, err) = trace_parse::<MimeParsing>("\"ab");
+            assert_eq!(trace.len(), 3);
+            assert_eq!(err, Some(CoreError::DoesNotEndWithDQuotes));
+        }
+
+        #[test]
+        fn custom_states_are_recorded() {
+            let (trace, err) = trace_parse::<MimeObsParsing>("\"\r\n a\"");
+            assert_eq!(err, None);
+            assert_eq!(trace, vec![
+                (0, State::Normal, false),
+                (1, custom(FWSState::HitCr), false),
+                (2, custom(FWSState::HitNl), false),
+                (3, State::Normal, true),
+                (4, State::Normal, true),
+                (5, State::End, false),
+            ]);
+        }
+    }
+
+    mod unquote_max_folds {
+        use qs::error::CoreError;
+        use super::super::super::{MimeParsing, MimeObsParsing};
+        use super::super::{unquote_max_folds, UnquoteLimitError, DEFAULT_MAX_FOLDS};
+
+        fn folded(folds: usize) -> String {
+            let mut input = String::from("\"a");
+            for _ in 0..folds {
+                input.push_str("\r\n b");
+            }
+            input.push('"');
+            input
+        }
+
+        #[test]
+        fn single_fold_passes() {
+            let res = unquote_max_folds::<MimeParsing>("\"a\r\n b\"", DEFAULT_MAX_FOLDS);
+            assert_eq!(res.unwrap(), "a b");
+            let res = unquote_max_folds::<MimeObsParsing>("\"a\r\n b\"", 1);
+            assert_eq!(res.unwrap(), "a b");
+            assert_eq!(unquote_max_folds::<MimeParsing>("abc", 0).unwrap(), "abc");
+        }
+
+        #[test]
+        fn limit_triggers() {
+            assert!(unquote_max_folds::<MimeParsing>(&folded(DEFAULT_MAX_FOLDS), DEFAULT_MAX_FOLDS).is_ok());
+            let res = unquote_max_folds::<MimeParsing>(&folded(DEFAULT_MAX_FOLDS + 1), DEFAULT_MAX_FOLDS);
+            assert_eq!(res, Err(UnquoteLimitError::ExceedsMaxFolds));
+            let res = unquote_max_folds::<MimeObsParsing>(&folded(3), 2);
+            assert_eq!(res, Err(UnquoteLimitError::ExceedsMaxFolds));
+        }
+
+        #[test]
+        fn stops_early_on_huge_input() {
+            // the missing closing '"' would be an error, too, but is never reached
+            let mut input = folded(100_000);
+            input.pop();
+            let res = unquote_max_folds::<MimeObsParsing>(&input, DEFAULT_MAX_FOLDS);
+            assert_eq!(res, Err(UnquoteLimitError::ExceedsMaxFolds));
+        }
+
+        #[test]
+        fn core_errors_are_passed_through() {
+            let res = unquote_max_folds::<MimeParsing>("\"a\rb\"", DEFAULT_MAX_FOLDS);
+            assert_eq!(res, Err(UnquoteLimitError::Core(CoreError::InvalidChar)));
+        }
+    }
+
+    mod unquote_limited {
+        use qs::error::CoreError;
+        use super::super::super::MimeParsing;
+        use super::super::{unquote_limited, UnquoteLimitError};
+
+        #[test]
+        fn within_limit() {
+            let res = unquote_limited::<MimeParsing>(r#""a\"b""#, 3).unwrap();
+            assert_eq!(res, r#"a"b"#);
+            let res = unquote_limited::<MimeParsing>("abc", 3).unwrap();
+            assert_eq!(res, "abc");
+        }
+
+        #[test]
+        fn decoded_bytes_are_counted() {
+            // 6 input bytes between the dquotes but only 3 decoded ones
+            let res = unquote_limited::<MimeParsing>(r#""\a\b\c""#, 3).unwrap();
+            assert_eq!(res, "abc");
+            let res = unquote_limited::<MimeParsing>(r#""\a\b\c""#, 2);
+            assert_eq!(res, Err(UnquoteLimitError::ExceedsMaxLen));
+        }
+
+        #[test]
+        fn bare_token_exceeding_limit() {
+            let res = unquote_limited::<MimeParsing>("abcd", 3);
+            assert_eq!(res, Err(UnquoteLimitError::ExceedsMaxLen));
+        }
+
+        #[test]
+        fn stops_early_on_huge_input() {
+            // the missing closing '"' would be an error, too, but is never reached
+            let mut input = String::from("\"");
+            for _ in 0..(1024 * 1024) {
+                input.push_str("\\a");
+            }
+            let res = unquote_limited::<MimeParsing>(&input, 10 * 1024);
+            assert_eq!(res, Err(UnquoteLimitError::ExceedsMaxLen));
+        }
+
+        #[test]
+        fn core_errors_are_passed_through() {
+            let res = unquote_limited::<MimeParsing>("\"abc", 10);
+            assert_eq!(res, Err(UnquoteLimitError::Core(CoreError::DoesNotEndWithDQuotes)));
+        }
+    }
+
+    mod unquote_with_offset {
+        use qs::error::CoreError;
+        use super::super::super::{MimeParsing, MimeParsingUtf8};
+        use super::super::{unquote_with_offset, OffsetError};
+
+        #[test]
+        fn ok() {
+            assert_eq!(unquote_with_offset::<MimeParsing>(r#""a\"b""#).unwrap(), r#"a"b"#);
+            assert_eq!(unquote_with_offset::<MimeParsing>("abc").unwrap(), "abc");
+        }
+
+        #[test]
+        fn invalid_char_mid_string() {
+            let res = unquote_with_offset::<MimeParsing>("\"ab\x07cd\"");
+            assert_eq!(res, Err(OffsetError::new(CoreError::InvalidChar, 3)));
+        }
+
+        #[test]
+        fn points_at_first_byte_of_multi_byte_char() {
+            let res = unquote_with_offset::<MimeParsing>("\"ab\u{e9}cd\"");
+            assert_eq!(res, Err(OffsetError::new(CoreError::InvalidChar, 3)));
+            let res = unquote_with_offset::<MimeParsing>("ab\u{e9}cd");
+            assert_eq!(res, Err(OffsetError::new(CoreError::InvalidChar, 2)));
+        }
+
+        #[test]
+        fn utf8_is_accepted_by_utf8_grammar() {
+            let res = unquote_with_offset::<MimeParsingUtf8>("\"ab\u{e9}cd\"");
+            assert_eq!(res.unwrap(), "ab\u{e9}cd");
+        }
+
+        #[test]
+        fn trailing_input() {
+            let res = unquote_with_offset::<MimeParsing>("\"ab\"cd");
+            assert_eq!(res, Err(OffsetError::new(CoreError::QuotedStringAlreadyEnded, 4)));
+        }
+
+        #[test]
+        fn unterminated_points_at_opening_dquote() {
+            let res = unquote_with_offset::<MimeParsing>("\"ab");
+            assert_eq!(res, Err(OffsetError::new(CoreError::DoesNotEndWithDQuotes, 0)));
+            let res = unquote_with_offset::<MimeParsing>("\"ab\\");
+            assert_eq!(res, Err(OffsetError::new(CoreError::DoesNotEndWithDQuotes, 0)));
+        }
+
+        #[test]
+        fn display_includes_offset() {
+            let err = unquote_with_offset::<MimeParsing>("\"ab\x07\"").unwrap_err();
+            assert!(err.to_string().ends_with(" at byte 3"));
+        }
+    }
+}
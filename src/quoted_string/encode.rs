@@ -0,0 +1,465 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::str;
+
+use qs::error::CoreError;
+use qs::spec::{
+    PartialCodePoint,
+    QuotingClassifier, QuotingClass,
+    WithoutQuotingValidator,
+};
+
+use super::{pcp_from_byte, is_valid_token};
+
+/// classifies a single char using the given classifier
+///
+/// This converts the char to a `PartialCodePoint`, non us-ascii chars are
+/// classified the same way `classify` does it.
+pub fn classify_char<Q: QuotingClassifier>(ch: char) -> QuotingClass {
+    Q::classify_for_quoting(PartialCodePoint::from_code_point(ch as u32))
+}
+
+/// classifies a whole value wrt. the given `QuotingClassifier`
+///
+/// Returns `Invalid` if any char is classified as `Invalid`, `NeedsQuoting` if any
+/// char needs quoting (or the value is empty, as a empty value can not be represented
+/// bare) and `QText` if every char is classified as `QText`.
+///
+/// This is the same classification `quote` uses to decide if it has to quote the value.
+pub fn classify<Q: QuotingClassifier>(value: &str) -> QuotingClass {
+    let mut class = if value.is_empty() {
+        QuotingClass::NeedsQuoting
+    } else {
+        QuotingClass::QText
+    };
+    for ch in value.chars() {
+        match classify_char::<Q>(ch) {
+            QuotingClass::QText => {},
+            QuotingClass::NeedsQuoting => class = QuotingClass::NeedsQuoting,
+            QuotingClass::Invalid => return QuotingClass::Invalid
+        }
+    }
+    class
+}
+
+/// extension trait for `QuotingClass` providing a readable form e.g. for logging
+///
+/// `QuotingClass` is defined in `quoted-string`, so `Display` can not be
+/// implemented for it in this crate, use `as_str` or `DisplayQuotingClass` instead.
+pub trait QuotingClassExt {
+    /// true if the class is `QuotingClass::Invalid`
+    fn is_invalid(&self) -> bool;
+
+    /// a human readable name of the class (e.g. `"needs quoting"`)
+    fn as_str(&self) -> &'static str;
+}
+
+impl QuotingClassExt for QuotingClass {
+    fn is_invalid(&self) -> bool {
+        *self == QuotingClass::Invalid
+    }
+
+    fn as_str(&self) -> &'static str {
+        match *self {
+            QuotingClass::QText => "qtext",
+            QuotingClass::NeedsQuoting => "needs quoting",
+            QuotingClass::Invalid => "invalid"
+        }
+    }
+}
+
+/// a wrapper implementing `Display` for a `QuotingClass`
+///
+/// It writes the same text `QuotingClassExt::as_str` returns, e.g.
+/// `format!("{}", DisplayQuotingClass(QuotingClass::QText))` is `"qtext"`.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct DisplayQuotingClass(pub QuotingClass);
+
+impl fmt::Display for DisplayQuotingClass {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        fter.write_str(self.0.as_str())
+    }
+}
+
+/// the result of classifying a whole byte slice wrt. a `QuotingClassifier`
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum SliceQuoting {
+    /// every byte is classified as `QText`
+    AllQText,
+    /// at last one byte needs quoting, but none is invalid
+    NeedsQuoting,
+    /// the byte at the given index is classified as `Invalid`
+    HasInvalid(usize)
+}
+
+/// classifies a whole byte slice wrt. the given `QuotingClassifier` in one pass
+///
+/// This stops at the first byte classified as `Invalid` returning it's index.
+/// Non us-ascii bytes are classified like non us-ascii chars, they are not
+/// validated to be utf-8. Unlike `classify` a empty slice is `AllQText`.
+pub fn classify_slice<Q: QuotingClassifier>(bytes: &[u8]) -> SliceQuoting {
+    let mut class = SliceQuoting::AllQText;
+    for (idx, bch) in bytes.iter().enumerate() {
+        match Q::classify_for_quoting(pcp_from_byte(*bch)) {
+            QuotingClass::QText => {},
+            QuotingClass::NeedsQuoting => class = SliceQuoting::NeedsQuoting,
+            QuotingClass::Invalid => return SliceQuoting::HasInvalid(idx)
+        }
+    }
+    class
+}
+
+/// quotes the value wrt. the given `QuotingClassifier` if needed
+///
+/// If every char of the value is classified as `QText` the value is returned as is,
+/// else wise it is wrapped in `'"'` and every char classified as `NeedsQuoting` is
+/// escaped with a quoted-pair. An empty value is always quoted (i.e. `""`).
+///
+/// Note that whether or not the value is quoted only depends on the classifier, so e.g.
+/// `"a b"` is returned bare with most classifiers. Use `quote_if_needed` with a
+/// `WithoutQuotingValidator` if the unquoted output has to be a token.
+///
+/// # Error
+///
+/// returns `CoreError::InvalidChar` if any char is classified as `Invalid`
+pub fn quote<Q: QuotingClassifier>(value: &str) -> Result<String, CoreError> {
+    let mut out = String::new();
+    quote_into::<Q>(value, &mut out)?;
+    Ok(out)
+}
+
+/// like `quote` but appends the output to the given buffer
+///
+/// The buffer is not cleared, so it can be used to build up composite output
+/// (e.g. a list of parameters). If a error is returned the buffer is unchanged.
+pub fn quote_into<Q: QuotingClassifier>(value: &str, out: &mut String) -> Result<(), CoreError> {
+    match classify::<Q>(value) {
+        QuotingClass::QText => {
+            out.push_str(value);
+            return Ok(());
+        },
+        QuotingClass::Invalid => return Err(CoreError::InvalidChar),
+        QuotingClass::NeedsQuoting => {}
+    }
+    push_quoted::<Q>(value, out);
+    Ok(())
+}
+
+/// returns the value bare if the `WithoutQuotingValidator` accepts it, else wise quotes it
+///
+/// If the validator accepts every char and `end` returns true the value is returned
+/// as `Cow::Borrowed`, else wise it is always quoted (even if every char is classified
+/// as `QText`). This allows e.g. emitting `charset=utf-8` as token but `name="a b"`
+/// quoted when using the `MimeTokenValidator`. A empty value is always quoted.
+///
+/// # Error
+///
+/// returns `CoreError::InvalidChar` if the value has to be quoted but a char is
+/// classified as `Invalid`
+pub fn quote_if_needed<'a, Q, V>(value: &'a str) -> Result<Cow<'a, str>, CoreError>
+    where Q: QuotingClassifier, V: WithoutQuotingValidator + Default
+{
+    if !value.is_empty() && is_valid_token::<V>(value) {
+        return Ok(Cow::Borrowed(value));
+    }
+    if classify::<Q>(value) == QuotingClass::Invalid {
+        return Err(CoreError::InvalidChar);
+    }
+    let mut out = String::new();
+    push_quoted::<Q>(value, &mut out);
+    Ok(Cow::Owned(out))
+}
+
+/// like `quote` but operates directly on bytes
+///
+/// Non us-ascii bytes are only accepted if the `QuotingClassifier` accepts non us-ascii
+/// chars (e.g. `MimeUtf8Quoting`) and have to form well-formed utf-8 sequences.
+///
+/// # Error
+///
+/// returns `CoreError::InvalidChar` if any byte is classified as `Invalid` or
+/// if the non us-ascii bytes are not valid utf-8
+pub fn quote_bytes<Q: QuotingClassifier>(value: &[u8]) -> Result<Vec<u8>, CoreError> {
+    let needs_quoting = match classify_slice::<Q>(value) {
+        SliceQuoting::AllQText => value.is_empty(),
+        SliceQuoting::NeedsQuoting => true,
+        SliceQuoting::HasInvalid(_) => return Err(CoreError::InvalidChar)
+    };
+    if !value.is_ascii() && str::from_utf8(value).is_err() {
+        return Err(CoreError::InvalidChar);
+    }
+
+    if !needs_quoting {
+        return Ok(value.to_vec());
+    }
+    let mut out = Vec::with_capacity(value.len() + 2);
+    out.push(b'"');
+    for bch in value {
+        if Q::classify_for_quoting(pcp_from_byte(*bch)) == QuotingClass::NeedsQuoting {
+            out.push(b'\\');
+        }
+        out.push(*bch);
+    }
+    out.push(b'"');
+    Ok(out)
+}
+
+fn push_quoted<Q: QuotingClassifier>(value: &str, out: &mut String) {
+    out.reserve(value.len() + 2);
+    out.push('"');
+    for ch in value.chars() {
+        let pcp = PartialCodePoint::from_code_point(ch as u32);
+        if Q::classify_for_quoting(pcp) == QuotingClass::NeedsQuoting {
+            out.push('\\');
+        }
+        out.push(ch);
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case)]
+
+    mod classify {
+        use qs::spec::QuotingClass;
+        use super::super::super::{
+            MimeQuoting, MimeUtf8Quoting,
+            MimeObsQuoting, MimeObsUtf8Quoting
+        };
+        use super::super::classify;
+
+        #[test]
+        fn clean_values_are_qtext() {
+            assert_eq!(classify::<MimeQuoting>("abc def"), QuotingClass::QText);
+            assert_eq!(classify::<MimeUtf8Quoting>("äbc"), QuotingClass::QText);
+            assert_eq!(classify::<MimeObsQuoting>("abc"), QuotingClass::QText);
+            assert_eq!(classify::<MimeObsUtf8Quoting>("äbc"), QuotingClass::QText);
+        }
+
+        #[test]
+        fn empty_value_needs_quoting() {
+            assert_eq!(classify::<MimeQuoting>(""), QuotingClass::NeedsQuoting);
+            assert_eq!(classify::<MimeObsUtf8Quoting>(""), QuotingClass::NeedsQuoting);
+        }
+
+        #[test]
+        fn escapes_need_quoting() {
+            assert_eq!(classify::<MimeQuoting>(r#"a"b"#), QuotingClass::NeedsQuoting);
+            assert_eq!(classify::<MimeUtf8Quoting>(r#"ä\b"#), QuotingClass::NeedsQuoting);
+            assert_eq!(classify::<MimeObsQuoting>("a\x01b"), QuotingClass::NeedsQuoting);
+            assert_eq!(classify::<MimeObsUtf8Quoting>("ä\x01\"b"), QuotingClass::NeedsQuoting);
+        }
+
+        #[test]
+        fn any_invalid_char_makes_it_invalid() {
+            assert_eq!(classify::<MimeQuoting>("\"ä"), QuotingClass::Invalid);
+            assert_eq!(classify::<MimeUtf8Quoting>("\"ä\0"), QuotingClass::Invalid);
+            assert_eq!(classify::<MimeObsQuoting>("\"\x01ä"), QuotingClass::Invalid);
+        }
+
+        #[test]
+        fn obs_utf8_never_is_invalid() {
+            assert_eq!(classify::<MimeObsUtf8Quoting>("ä\"\x01\0"), QuotingClass::NeedsQuoting);
+        }
+    }
+
+    mod QuotingClassExt {
+        use qs::spec::QuotingClass;
+        use super::super::{QuotingClassExt, DisplayQuotingClass};
+
+        #[test]
+        fn as_str() {
+            assert_eq!(QuotingClass::QText.as_str(), "qtext");
+            assert_eq!(QuotingClass::NeedsQuoting.as_str(), "needs quoting");
+            assert_eq!(QuotingClass::Invalid.as_str(), "invalid");
+            assert_eq!(format!("class: {}", QuotingClass::Invalid.as_str()), "class: invalid");
+        }
+
+        #[test]
+        fn display_wrapper() {
+            let classes = [QuotingClass::QText, QuotingClass::NeedsQuoting, QuotingClass::Invalid];
+            for class in classes.iter() {
+                assert_eq!(DisplayQuotingClass(class.clone()).to_string(), class.as_str());
+            }
+            let text = format!("class: {}", DisplayQuotingClass(QuotingClass::NeedsQuoting));
+            assert_eq!(text, "class: needs quoting");
+        }
+
+        #[test]
+        fn is_invalid() {
+            assert!(QuotingClass::Invalid.is_invalid());
+            assert!(!QuotingClass::QText.is_invalid());
+            assert!(!QuotingClass::NeedsQuoting.is_invalid());
+        }
+    }
+
+    mod quote_into {
+        use qs::error::CoreError;
+        use super::super::super::MimeQuoting;
+        use super::super::quote_into;
+
+        #[test]
+        fn appends_to_buffer() {
+            let mut out = String::new();
+            for value in &["a", "b c", "d\"e", ""] {
+                out.push(';');
+                quote_into::<MimeQuoting>(value, &mut out).unwrap();
+            }
+            assert_eq!(out, r#";a;b c;"d\"e";"""#);
+        }
+
+        #[test]
+        fn buffer_is_unchanged_on_error() {
+            let mut out = String::from("x=");
+            let res = quote_into::<MimeQuoting>("a\"\0", &mut out);
+            assert_eq!(res, Err(CoreError::InvalidChar));
+            assert_eq!(out, "x=");
+        }
+    }
+
+    mod quote_if_needed {
+        use std::borrow::Cow;
+        use qs::error::CoreError;
+        use super::super::super::{MimeQuoting, MimeTokenValidator};
+        use super::super::quote_if_needed;
+
+        #[test]
+        fn token_stays_bare() {
+            let res = quote_if_needed::<MimeQuoting, MimeTokenValidator>("utf-8").unwrap();
+            assert!(matches!(res, Cow::Borrowed("utf-8")));
+        }
+
+        #[test]
+        fn non_token_is_quoted() {
+            let res = quote_if_needed::<MimeQuoting, MimeTokenValidator>("a b").unwrap();
+            assert_eq!(res, Cow::<str>::Owned("\"a b\"".into()));
+            let res = quote_if_needed::<MimeQuoting, MimeTokenValidator>(r#"a"b"#).unwrap();
+            assert_eq!(res, Cow::<str>::Owned(r#""a\"b""#.into()));
+        }
+
+        #[test]
+        fn empty_is_quoted() {
+            let res = quote_if_needed::<MimeQuoting, MimeTokenValidator>("").unwrap();
+            assert_eq!(res, Cow::<str>::Owned("\"\"".into()));
+        }
+
+        #[test]
+        fn invalid_chars_are_rejected() {
+            let res = quote_if_needed::<MimeQuoting, MimeTokenValidator>("a\0");
+            assert_eq!(res, Err(CoreError::InvalidChar));
+        }
+    }
+
+    mod classify_slice {
+        use super::super::super::{MimeQuoting, MimeUtf8Quoting, MimeObsQuoting};
+        use super::super::{classify_slice, SliceQuoting};
+
+        #[test]
+        fn all_qtext() {
+            assert_eq!(classify_slice::<MimeQuoting>(b"abc def"), SliceQuoting::AllQText);
+            assert_eq!(classify_slice::<MimeQuoting>(b""), SliceQuoting::AllQText);
+            assert_eq!(classify_slice::<MimeUtf8Quoting>("äb".as_bytes()), SliceQuoting::AllQText);
+        }
+
+        #[test]
+        fn needs_quoting() {
+            assert_eq!(classify_slice::<MimeQuoting>(br#"a"b\c"#), SliceQuoting::NeedsQuoting);
+            assert_eq!(classify_slice::<MimeObsQuoting>(b"a\x01"), SliceQuoting::NeedsQuoting);
+        }
+
+        #[test]
+        fn index_of_first_invalid() {
+            assert_eq!(classify_slice::<MimeQuoting>(b"a\"b\0c\0"), SliceQuoting::HasInvalid(3));
+            assert_eq!(classify_slice::<MimeQuoting>("aä".as_bytes()), SliceQuoting::HasInvalid(1));
+            assert_eq!(classify_slice::<MimeObsQuoting>(b"\xFF"), SliceQuoting::HasInvalid(0));
+        }
+    }
+
+    mod quote_bytes {
+        use qs::error::CoreError;
+        use super::super::super::{MimeQuoting, MimeUtf8Quoting};
+        use super::super::quote_bytes;
+
+        #[test]
+        fn same_as_quote_for_us_ascii() {
+            assert_eq!(quote_bytes::<MimeQuoting>(b"simple").unwrap(), b"simple".to_vec());
+            assert_eq!(quote_bytes::<MimeQuoting>(br#"a"b"#).unwrap(), br#""a\"b""#.to_vec());
+            assert_eq!(quote_bytes::<MimeQuoting>(b"").unwrap(), b"\"\"".to_vec());
+        }
+
+        #[test]
+        fn utf8_only_with_utf8_classifier() {
+            let res = quote_bytes::<MimeUtf8Quoting>("ä\"".as_bytes()).unwrap();
+            assert_eq!(res, "\"ä\\\"\"".as_bytes().to_vec());
+            let res = quote_bytes::<MimeQuoting>("ä".as_bytes());
+            assert_eq!(res, Err(CoreError::InvalidChar));
+        }
+
+        #[test]
+        fn invalid_utf8_is_rejected() {
+            let res = quote_bytes::<MimeUtf8Quoting>(b"a\xC3\x28");
+            assert_eq!(res, Err(CoreError::InvalidChar));
+        }
+    }
+
+    mod quote {
+        use qs::error::CoreError;
+        use qs::spec::GeneralQSSpec;
+        use qs::to_content;
+        use super::super::super::{MimeQuoting, MimeObsQuoting, MimeParsing};
+        use super::super::quote;
+
+        #[derive(Copy, Clone, Debug)]
+        struct MimeSpec;
+
+        impl GeneralQSSpec for MimeSpec {
+            type Quoting = MimeQuoting;
+            type Parsing = MimeParsing;
+        }
+
+        #[test]
+        fn qtext_only_is_not_quoted() {
+            assert_eq!(quote::<MimeQuoting>("simple").unwrap(), "simple");
+        }
+
+        #[test]
+        fn empty_value_is_quoted() {
+            assert_eq!(quote::<MimeQuoting>("").unwrap(), "\"\"");
+        }
+
+        #[test]
+        fn dquotes_and_escape_are_quoted() {
+            let res = quote::<MimeQuoting>(r#"a"b\c"#).unwrap();
+            assert_eq!(res, r#""a\"b\\c""#);
+        }
+
+        #[test]
+        fn invalid_chars_are_rejected() {
+            assert_eq!(quote::<MimeQuoting>("a\0b"), Err(CoreError::InvalidChar));
+            assert_eq!(quote::<MimeQuoting>("äb"), Err(CoreError::InvalidChar));
+        }
+
+        #[test]
+        fn ctls_are_quoted_in_obs_grammar() {
+            let res = quote::<MimeObsQuoting>("a\x01b").unwrap();
+            assert_eq!(res, "\"a\\\x01b\"");
+        }
+
+        #[test]
+        fn same_output_for_modern_and_obs_grammar() {
+            let expected = r#""he said \"hi\"""#;
+            assert_eq!(quote::<MimeQuoting>(r#"he said "hi""#).unwrap(), expected);
+            assert_eq!(quote::<MimeObsQuoting>(r#"he said "hi""#).unwrap(), expected);
+        }
+
+        #[test]
+        fn quoted_output_round_trips() {
+            for input in &["\"", "\\", r#"he said "hi""#, r#"C:\some\path"#, ""] {
+                let quoted = quote::<MimeQuoting>(input).unwrap();
+                let content = to_content::<MimeSpec>(&quoted).unwrap();
+                assert_eq!(&*content, *input);
+            }
+        }
+    }
+}
@@ -1,8 +1,9 @@
-use lut::{Table};
+use lut::{Table, Access};
 use lookup_tables::{
     MediaTypeChars,
-    QTextWs,
-    HttpToken
+    QTextWs, VCharWs,
+    DQuoteOrEscape,
+    HttpToken, AlphaNum
 };
 use qs::error::CoreError;
 use qs::spec::{
@@ -10,6 +11,7 @@ use qs::spec::{
     ParsingImpl,
     State,
     WithoutQuotingValidator,
+    QuotingClassifier, QuotingClass,
 };
 
 /// a zero-sized type to provide a `ParsingImpl` for media types wrt. the (obs) Http grammar
@@ -35,9 +37,60 @@ impl ParsingImpl for HttpObsParsingImpl {
     }
 }
 
-/// a zero-sized type to provide a `WithoutQuotingValidator` impl for tokens (http grammar)
+/// a zero-sized type to provide a `ParsingImpl` for quoted-strings wrt. RFC 7230
+///
+/// It works on bytes, so any non us-ascii byte is treated as obs-text which can appear
+/// literally or in a quoted-pair. There are no FWS in http so any `'\r'`/`'\n'` is invalid.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Default)]
-pub struct HttpTokenValidator;
+pub struct HttpQuotedString;
+
+impl ParsingImpl for HttpQuotedString {
+
+    /// any VChar, ws and obs-text can be quoted
+    fn can_be_quoted(bch: PartialCodePoint) -> bool {
+        let idx = bch.as_u8() as usize;
+        idx > 0x7f || MediaTypeChars::check_at(idx, VCharWs)
+    }
+
+    /// any qtext, ws and obs-text can appear without quoting
+    fn handle_normal_state(bch: PartialCodePoint) -> Result<(State<Self>, bool), CoreError> {
+        let idx = bch.as_u8() as usize;
+        if idx > 0x7f || MediaTypeChars::check_at(idx, QTextWs) {
+            Ok((State::Normal, true))
+        } else {
+            Err(CoreError::InvalidChar)
+        }
+    }
+}
+
+/// a zero-sized type to provide a `QuotingClassifier` for quoted-strings wrt. RFC 7230
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Default)]
+pub struct HttpQuotedStringQuoting;
+
+impl QuotingClassifier for HttpQuotedStringQuoting {
+    fn classify_for_quoting(pcp: PartialCodePoint) -> QuotingClass {
+        let idx = pcp.as_u8() as usize;
+        if idx > 0x7f {
+            return QuotingClass::QText;
+        }
+        let lres = MediaTypeChars::lookup(idx);
+        if QTextWs.check(lres) {
+            QuotingClass::QText
+        } else if DQuoteOrEscape.check(lres) {
+            QuotingClass::NeedsQuoting
+        } else {
+            QuotingClass::Invalid
+        }
+    }
+}
+
+/// a type providing a `WithoutQuotingValidator` impl for tokens (http grammar)
+///
+/// As RFC 7230 tokens consist of at least one char a empty input is invalid.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Default)]
+pub struct HttpTokenValidator {
+    count: usize
+}
 
 impl HttpTokenValidator {
     /// create a new HttpTokenValidator
@@ -48,10 +101,203 @@ impl HttpTokenValidator {
 
 impl WithoutQuotingValidator for HttpTokenValidator {
     fn next(&mut self, pcp: PartialCodePoint) -> bool {
-        MediaTypeChars::check_at(pcp.as_u8() as usize, HttpToken)
+        let res = MediaTypeChars::check_at(pcp.as_u8() as usize, HttpToken);
+        if res {
+            self.count += 1;
+        }
+        res
     }
 
     fn end(&self) -> bool {
-        true
+        self.count > 0
+    }
+}
+
+/// a type providing a `WithoutQuotingValidator` for `token68` (RFC 7235)
+///
+/// `token68` is used e.g. for the credentials in `Authorization: Bearer <token68>`,
+/// it consists of alphanumeric chars and `"-._~+/"` followed by any number of `'='`
+/// used as padding. A input consisting only of `'='` (or a empty input) is invalid.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Default)]
+pub struct Token68Validator {
+    count: usize,
+    in_padding: bool
+}
+
+impl Token68Validator {
+    /// create a new Token68Validator
+    pub fn new() -> Self {
+        Default::default()
     }
-}
\ No newline at end of file
+}
+
+impl WithoutQuotingValidator for Token68Validator {
+    fn next(&mut self, pcp: PartialCodePoint) -> bool {
+        let iu8 = pcp.as_u8();
+        if iu8 == b'=' {
+            self.in_padding = true;
+            true
+        } else if !self.in_padding
+            && (MediaTypeChars::check_at(iu8 as usize, AlphaNum) || b"-._~+/".contains(&iu8))
+        {
+            self.count += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn end(&self) -> bool {
+        self.count > 0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case)]
+
+    mod HttpTokenValidator {
+        use super::super::super::{is_valid_token, MimeTokenValidator};
+        use super::super::HttpTokenValidator;
+
+        #[test]
+        fn allowed_punctuation_passes() {
+            assert!(is_valid_token::<HttpTokenValidator>("!#$%&'*+-.^_`|~"));
+            assert!(is_valid_token::<HttpTokenValidator>("gzip"));
+            assert!(is_valid_token::<HttpTokenValidator>("X-Custom-1"));
+        }
+
+        #[test]
+        fn empty_is_rejected() {
+            assert!(!is_valid_token::<HttpTokenValidator>(""));
+        }
+
+        #[test]
+        fn braces_and_parens_are_rejected() {
+            for input in &["{", "}", "(", ")", "a{b}", "a(b)"] {
+                assert!(!is_valid_token::<HttpTokenValidator>(input), "{:?}", input);
+            }
+        }
+
+        #[test]
+        fn mime_only_token_chars_are_rejected() {
+            // '{' and '}' are mime token chars but not http token chars
+            assert!(is_valid_token::<MimeTokenValidator>("a{b}"));
+            assert!(!is_valid_token::<HttpTokenValidator>("a{b}"));
+        }
+
+        #[test]
+        fn separators_and_non_us_ascii_are_rejected() {
+            for input in &["a b", "a/b", "a;b", "a=b", "a\"b", "aä"] {
+                assert!(!is_valid_token::<HttpTokenValidator>(input), "{:?}", input);
+            }
+        }
+    }
+
+    mod Token68Validator {
+        use super::super::super::is_valid_token;
+        use super::super::Token68Validator;
+
+        fn is_token68(input: &str) -> bool {
+            is_valid_token::<Token68Validator>(input)
+        }
+
+        #[test]
+        fn base64url_with_padding() {
+            assert!(is_token68("dGVzdA-_=="));
+            assert!(is_token68("YWJj+/~.x"));
+            assert!(is_token68("abc"));
+        }
+
+        #[test]
+        fn only_padding_is_invalid() {
+            assert!(!is_token68("===="));
+            assert!(!is_token68("="));
+            assert!(!is_token68(""));
+        }
+
+        #[test]
+        fn padding_has_to_be_trailing() {
+            assert!(!is_token68("ab=c"));
+            assert!(!is_token68("=abc"));
+        }
+
+        #[test]
+        fn other_chars_are_invalid() {
+            for input in &["a b", "a,b", "a\"b", "a!b", "a%b", "aä"] {
+                assert!(!is_token68(input), "{:?}", input);
+            }
+        }
+    }
+
+    mod HttpQuotedString {
+        use qs::error::CoreError;
+        use super::super::super::unquote;
+        use super::super::HttpQuotedString;
+
+        #[test]
+        fn obs_text_is_allowed() {
+            let res = unquote::<HttpQuotedString>("\"a\u{a0}b\"");
+            assert_eq!(res.unwrap(), "a\u{a0}b");
+        }
+
+        #[test]
+        fn escaped_dquotes() {
+            let res = unquote::<HttpQuotedString>(r#""a\"b\\c""#);
+            assert_eq!(res.unwrap(), r#"a"b\c"#);
+        }
+
+        #[test]
+        fn escaped_obs_text() {
+            let res = unquote::<HttpQuotedString>("\"a\\\u{a0}b\"");
+            assert_eq!(res.unwrap(), "a\u{a0}b");
+        }
+
+        #[test]
+        fn folding_accepted_by_mime_obs_is_rejected() {
+            use super::super::super::MimeObsParsing;
+            for input in &["\"a\r\n b\"", "\"a\r\n\tb\"", "\"\r\n \r\n \""] {
+                assert!(unquote::<MimeObsParsing>(input).is_ok(), "{:?}", input);
+                assert_eq!(unquote::<HttpQuotedString>(input), Err(CoreError::InvalidChar), "{:?}", input);
+            }
+        }
+
+        #[test]
+        fn cr_and_lf_are_invalid() {
+            for input in &["\"a\rb\"", "\"a\nb\"", "\"a\r\n b\""] {
+                assert_eq!(unquote::<HttpQuotedString>(input), Err(CoreError::InvalidChar));
+            }
+        }
+    }
+
+    mod HttpQuotedStringQuoting {
+        //it's not unused rustc just falls over wrt. the unused detection and `use super::*`
+        #[allow(unused_imports)]
+        use qs::spec::QuotingClassifier;
+        use super::super::{HttpQuotedStringQuoting, PartialCodePoint, QuotingClass};
+
+        fn classify(bch: u8) -> QuotingClass {
+            HttpQuotedStringQuoting::classify_for_quoting(PartialCodePoint::from_utf8_byte(bch))
+        }
+
+        #[test]
+        fn obs_text_is_qtext() {
+            assert_eq!(classify(0xA0), QuotingClass::QText);
+            assert_eq!(classify(0x80), QuotingClass::QText);
+        }
+
+        #[test]
+        fn dquotes_and_escape_needs_quoting() {
+            assert_eq!(classify(b'"'), QuotingClass::NeedsQuoting);
+            assert_eq!(classify(b'\\'), QuotingClass::NeedsQuoting);
+        }
+
+        #[test]
+        fn ctls_are_invalid() {
+            for x in b"\0\r\n\x7f" {
+                assert_eq!(classify(*x), QuotingClass::Invalid);
+            }
+            assert_eq!(classify(b'\t'), QuotingClass::QText);
+        }
+    }
+}
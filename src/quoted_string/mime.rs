@@ -1,25 +1,30 @@
-use lut::{Table, Any};
+use std::borrow::Cow;
+
+use lut::{Table, Access, Any};
 use lookup_tables::{
     MediaTypeChars,
     QText,
     QTextWs,
     DQuoteOrEscape, Ws,
-    Token
+    Token, RestrictedName, TSpecials,
+    BChars
 };
 use qs::error::CoreError;
 use qs::spec::{
     PartialCodePoint,
-    ParsingImpl,
-    State,
     WithoutQuotingValidator,
     QuotingClassifier, QuotingClass,
 };
 
-use super::{MimeParsingExt, FWSState};
+use super::{unquote, unquote_cow, unquote_with_offset, quote_if_needed, OffsetError};
 
 /// a type providing a `WithoutQuotingValidator` for token wrt. the mime grammar
+///
+/// Empty tokens are invalid.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Default)]
-pub struct MimeTokenValidator;
+pub struct MimeTokenValidator {
+    count: usize
+}
 
 impl MimeTokenValidator {
     /// create a new MimeTokenValidator
@@ -30,13 +35,176 @@ impl MimeTokenValidator {
 
 impl WithoutQuotingValidator for MimeTokenValidator {
     fn next(&mut self, pcp: PartialCodePoint) -> bool {
-        MediaTypeChars::check_at(pcp.as_u8() as usize, Token)
+        let res = MediaTypeChars::check_at(pcp.as_u8() as usize, Token);
+        if res {
+            self.count += 1;
+        }
+        res
+    }
+    fn end(&self) -> bool {
+        self.count > 0
+    }
+}
+
+/// a type providing a `WithoutQuotingValidator` for restricted-names (RFC 6838)
+///
+/// Registered media types (and their subtypes) have to be restricted-names, i.e.
+/// they start with a alphanumeric char followed by up to 126 `RestrictedName` chars.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Default)]
+pub struct MimeRestrictedNameValidator {
+    count: usize
+}
+
+impl MimeRestrictedNameValidator {
+    /// create a new MimeRestrictedNameValidator
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl WithoutQuotingValidator for MimeRestrictedNameValidator {
+    fn next(&mut self, pcp: PartialCodePoint) -> bool {
+        let iu8 = pcp.as_u8();
+        let res =
+            if self.count == 0 {
+                iu8 < 0x7f && (iu8 as char).is_alphanumeric()
+            } else {
+                MediaTypeChars::check_at(iu8 as usize, RestrictedName)
+            };
+        if res {
+            self.count += 1;
+        }
+        res
+    }
+
+    fn end(&self) -> bool {
+        0 < self.count && self.count < 128
+    }
+}
+
+/// alias for `MimeRestrictedNameValidator`, named after the `RestrictedToken` class
+pub type MimeRestrictedTokenValidator = MimeRestrictedNameValidator;
+
+/// a type providing a `WithoutQuotingValidator` for charset parameter values
+///
+/// Charsets are (mime) tokens but are limited to chars up to `'z'`, so no charset
+/// value should ever need quoting. Empty values are invalid.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Default)]
+pub struct CharsetTokenValidator {
+    count: usize
+}
+
+impl CharsetTokenValidator {
+    /// create a new CharsetTokenValidator
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl WithoutQuotingValidator for CharsetTokenValidator {
+    fn next(&mut self, pcp: PartialCodePoint) -> bool {
+        let iu8 = pcp.as_u8();
+        let res = iu8 <= b'z' && MediaTypeChars::check_at(iu8 as usize, Token);
+        if res {
+            self.count += 1;
+        }
+        res
     }
+
     fn end(&self) -> bool {
-        true
+        self.count > 0
     }
 }
 
+/// a type providing a `WithoutQuotingValidator` for tokens strictly following RFC 2045
+///
+/// A byte is accepted if it is us-ascii and neither a CTL, SPACE nor in `TSpecials`,
+/// this e.g. rejects `'/'` in parameter names which some parsers wrongly accept.
+/// Empty tokens are invalid.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Default)]
+pub struct Rfc2045TokenValidator {
+    count: usize
+}
+
+impl Rfc2045TokenValidator {
+    /// create a new Rfc2045TokenValidator
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl WithoutQuotingValidator for Rfc2045TokenValidator {
+    fn next(&mut self, pcp: PartialCodePoint) -> bool {
+        let iu8 = pcp.as_u8();
+        let res = b' ' < iu8 && iu8 < 0x7f
+            && !MediaTypeChars::check_at(iu8 as usize, TSpecials);
+        if res {
+            self.count += 1;
+        }
+        res
+    }
+
+    fn end(&self) -> bool {
+        self.count > 0
+    }
+}
+
+/// a type providing a `WithoutQuotingValidator` for multipart boundaries (RFC 2046)
+///
+/// A boundary consists of 1 to 70 `bchars`, i.e. `bcharsnospace` (alphanumeric chars
+/// and `'()+_,-./:=?`) or `' '`, but must not end with a `' '`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Default)]
+pub struct BoundaryValidator {
+    count: usize,
+    last_was_space: bool
+}
+
+impl BoundaryValidator {
+    /// create a new BoundaryValidator
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl WithoutQuotingValidator for BoundaryValidator {
+    fn next(&mut self, pcp: PartialCodePoint) -> bool {
+        let iu8 = pcp.as_u8();
+        let res = MediaTypeChars::check_at(iu8 as usize, BChars);
+        if res {
+            self.count += 1;
+            self.last_was_space = iu8 == b' ';
+        }
+        res
+    }
+
+    fn end(&self) -> bool {
+        0 < self.count && self.count <= 70 && !self.last_was_space
+    }
+}
+
+/// generates a valid multipart boundary deterministically from the given seed
+///
+/// The boundary consists of a `"=_"` prefix followed by 30 alphanumeric chars,
+/// as `"=_"` can not appear in quoted-printable or base64 encoded bodies.
+/// This is meant for tests and reproducible output, it is **not** suitable
+/// to generate boundaries for untrusted content as they are predictable.
+pub fn generate_boundary(seed: u64) -> String {
+    const ALPHABET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+    let mut state = seed;
+    let mut out = String::with_capacity(32);
+    out.push_str("=_");
+    for _ in 0..30 {
+        // xorshift64* with a non zero state
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut x = state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        let rand = x.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        out.push(ALPHABET[(rand >> 32) as usize % ALPHABET.len()] as char);
+    }
+    out
+}
 
 /// a type providing a `QuotingClassifier` impl wrt. the obs mime grammar
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Default)]
@@ -70,51 +238,185 @@ impl QuotingClassifier for MimeObsUtf8Quoting {
     }
 }
 
+/// a type providing a `QuotingClassifier` impl wrt. the modern, us-ascii mime grammar
+///
+/// Unlike `MimeObsQuoting` CTLs (except `'\t'`) are `Invalid` as the modern grammar
+/// does not allow them in quoted-pairs, so producers using it refuse to emit values
+/// containing e.g. a NUL or a bare newline.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Default)]
+pub struct MimeQuoting;
+
+impl QuotingClassifier for MimeQuoting {
+    fn classify_for_quoting(pcp: PartialCodePoint) -> QuotingClass {
+        let lres = MediaTypeChars::lookup(pcp.as_u8() as usize);
+        if QTextWs.check(lres) {
+            QuotingClass::QText
+        } else if DQuoteOrEscape.check(lres) {
+            QuotingClass::NeedsQuoting
+        } else {
+            QuotingClass::Invalid
+        }
+    }
+}
+
+/// the classifier for strict modern output, an alias for `MimeQuoting`
+///
+/// CTLs (except `'\t'`) are `Invalid`, unlike with `MimeObsQuoting` where they
+/// are `NeedsQuoting`, so e.g. a value containing a NUL or a bare newline is
+/// refused instead of being emitted as quoted-pair.
+pub type MimeStrictQuoting = MimeQuoting;
+
+/// a type providing a `QuotingClassifier` impl wrt. the internationalized, modern mime grammar
+///
+/// Like `MimeQuoting` but all non us-ascii chars are `QText`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Default)]
+pub struct MimeUtf8Quoting;
+
+impl QuotingClassifier for MimeUtf8Quoting {
+    fn classify_for_quoting(pcp: PartialCodePoint) -> QuotingClass {
+        let iu8 = pcp.as_u8();
+        if iu8 > 0x7f {
+            QuotingClass::QText
+        } else {
+            MimeQuoting::classify_for_quoting(pcp)
+        }
+    }
+}
+
+/// a type providing a `QuotingClassifier` impl which reports whitespace as `NeedsQuoting`
+///
+/// Like `MimeQuoting` but `' '` and `'\t'` are `NeedsQuoting` instead of `QText`.
+/// This is meant for producers which want to make sure any value containing
+/// whitespace is wrapped in `'"'`, as some systems mangle unquoted whitespace.
+///
+/// Note that `quote` escapes every char which needs quoting, so with this classifier
+/// the ws is written as quoted-pair (e.g. `"foo\ bar"`), which is valid in all mime
+/// grammars.
+///
+/// It also implements `WithoutQuotingValidator` by delegating to `MimeTokenValidator`
+/// (token chars never include whitespace), so it can be used as both type parameters
+/// of `quote_if_needed` to emit tokens bare and quote everything else.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Default)]
+pub struct MimeQuoteWhitespace {
+    token: MimeTokenValidator
+}
+
+impl MimeQuoteWhitespace {
+    /// create a new MimeQuoteWhitespace
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl QuotingClassifier for MimeQuoteWhitespace {
+    fn classify_for_quoting(pcp: PartialCodePoint) -> QuotingClass {
+        if MediaTypeChars::check_at(pcp.as_u8() as usize, Ws) {
+            QuotingClass::NeedsQuoting
+        } else {
+            MimeQuoting::classify_for_quoting(pcp)
+        }
+    }
+}
+
+impl WithoutQuotingValidator for MimeQuoteWhitespace {
+    fn next(&mut self, pcp: PartialCodePoint) -> bool {
+        self.token.next(pcp)
+    }
+
+    fn end(&self) -> bool {
+        self.token.end()
+    }
+}
+
 
 
-macro_rules! def_mime_parsing {
+/// defines a new `ParsingImpl` for a mime like quoted-string grammar
+///
+/// The defined type wraps a `FWSState` and implements `MimeParsingExt` with the
+/// given flags and `ParsingImpl` using the given body for `can_be_quoted`. This
+/// is how the mime grammars of this crate are defined, so it can be used to
+/// define variations of them (e.g. for a legacy vendor header) without having
+/// to implement `MimeParsingExt` by hand.
+///
+/// # Example
+///
+/// ```
+/// #[macro_use]
+/// extern crate media_type_impl_utils;
+///
+/// use media_type_impl_utils::quoted_string::unquote;
+///
+/// define_mime_grammar! {
+///     /// a legacy grammar which only allows quoting `'"'` and `'\\'`
+///     pub struct LegacyParsing {
+///         utf8 = false;
+///         obsolete_syntax = false;
+///         folding_ws = false;
+///     }
+///     fn can_be_quoted(bch: PartialCodePoint) -> bool {
+///         bch.as_u8() == b'"' || bch.as_u8() == b'\\'
+///     }
+/// }
+///
+/// fn main() {
+///     assert_eq!(unquote::<LegacyParsing>(r#""a\"b""#).unwrap(), "a\"b");
+///     assert!(unquote::<LegacyParsing>(r#""a\b""#).is_err());
+/// }
+/// ```
+#[macro_export]
+macro_rules! define_mime_grammar {
     (
         $(#[$meta:meta])*
         pub struct $name:ident {
             utf8 = $utf8:tt;
-            obsolte_syntax = $obs:tt;
+            obsolete_syntax = $obs:tt;
+            folding_ws = $fws:tt;
         }
         fn can_be_quoted($nm:ident: PartialCodePoint) -> bool
             $body:block
     ) => (
         $(#[$meta])*
         #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
-        pub struct $name(FWSState);
-        impl MimeParsingExt for $name {
+        pub struct $name($crate::quoted_string::FWSState);
+
+        impl $crate::quoted_string::MimeParsingExt for $name {
             const ALLOW_UTF8: bool = $utf8;
             const OBS: bool = $obs;
+            const ALLOW_FWS: bool = $fws;
 
-            fn custom_state(state: FWSState, emit: bool) -> (State<Self>, bool) {
-                (State::Custom($name(state)), emit)
+            fn custom_state(state: $crate::quoted_string::FWSState, emit: bool)
+                -> ($crate::__macro_support::State<Self>, bool)
+            {
+                ($crate::__macro_support::State::Custom($name(state)), emit)
             }
         }
 
-        impl ParsingImpl for $name {
-            fn can_be_quoted($nm: PartialCodePoint) -> bool {
+        impl $crate::__macro_support::ParsingImpl for $name {
+            fn can_be_quoted($nm: $crate::__macro_support::PartialCodePoint) -> bool {
                 $body
             }
 
-            fn handle_normal_state(bch: PartialCodePoint) -> Result<(State<Self>, bool), CoreError> {
-                <Self as MimeParsingExt>::handle_normal_state(bch)
+            fn handle_normal_state(bch: $crate::__macro_support::PartialCodePoint)
+                -> Result<($crate::__macro_support::State<Self>, bool), $crate::__macro_support::CoreError>
+            {
+                <Self as $crate::quoted_string::MimeParsingExt>::handle_normal_state(bch)
             }
 
-            fn advance(&self, bch: PartialCodePoint) -> Result<(State<Self>, bool), CoreError> {
+            fn advance(&self, bch: $crate::__macro_support::PartialCodePoint)
+                -> Result<($crate::__macro_support::State<Self>, bool), $crate::__macro_support::CoreError>
+            {
                 self.0.advance(bch)
             }
         }
     );
 }
 
-def_mime_parsing! {
+define_mime_grammar! {
     /// a type providing a `ParsingImpl`/`MimeParsingExt` impl wrt. the obs mime grammar
     pub struct MimeObsParsing {
         utf8 = false;
-        obsolte_syntax = true;
+        obsolete_syntax = true;
+        folding_ws = true;
     }
     fn can_be_quoted(bch: PartialCodePoint) -> bool {
         // obs syntax allows any us-ascii in quoted-pairs
@@ -122,11 +424,12 @@ def_mime_parsing! {
     }
 }
 
-def_mime_parsing! {
+define_mime_grammar! {
     /// a type providing a `ParsingImpl`/`MimeParsingExt` impl wrt. the internationalized obs mime grammar
     pub struct MimeObsParsingUtf8 {
         utf8 = true;
-        obsolte_syntax = true;
+        obsolete_syntax = true;
+        folding_ws = true;
     }
     fn can_be_quoted(bch: PartialCodePoint) -> bool {
         // Internationalized Mail does not extend quoted-pairs just qtext ...
@@ -135,11 +438,12 @@ def_mime_parsing! {
     }
 }
 
-def_mime_parsing! {
+define_mime_grammar! {
     /// a type providing a `ParsingImpl`/`MimeParsingExt` impl wrt. the modern, us-ascii mime grammar
     pub struct MimeParsing {
         utf8 = false;
-        obsolte_syntax = false;
+        obsolete_syntax = false;
+        folding_ws = true;
     }
     fn can_be_quoted(bch: PartialCodePoint) -> bool {
         // VCHAR / WS == QText + Ws + DQuoteOrEscape
@@ -148,11 +452,12 @@ def_mime_parsing! {
     }
 }
 
-def_mime_parsing! {
+define_mime_grammar! {
     /// a type providing a `ParsingImpl`/`MimeParsingExt` impl wrt. the internationalized, modern mime grammar
     pub struct MimeParsingUtf8 {
         utf8 = true;
-        obsolte_syntax = false;
+        obsolete_syntax = false;
+        folding_ws = true;
     }
     fn can_be_quoted(bch: PartialCodePoint) -> bool {
         // Internationalized Mail does not extend quoted-pairs just qtext ...
@@ -161,3 +466,655 @@ def_mime_parsing! {
     }
 }
 
+define_mime_grammar! {
+    /// a type providing a `ParsingImpl`/`MimeParsingExt` impl for Content-Disposition parameters
+    ///
+    /// This is the modern, us-ascii mime grammar, but as used in http (RFC 6266) without
+    /// allowing any FWS.
+    pub struct DispositionParsing {
+        utf8 = false;
+        obsolete_syntax = false;
+        folding_ws = false;
+    }
+    fn can_be_quoted(bch: PartialCodePoint) -> bool {
+        let idx = bch.as_u8() as usize;
+        MediaTypeChars::check_at(idx, Any::new(Ws) | QText | DQuoteOrEscape)
+    }
+}
+
+define_mime_grammar! {
+    /// a type providing a `ParsingImpl`/`MimeParsingExt` impl for internationalized Content-Disposition parameters
+    ///
+    /// Like `DispositionParsing` but non us-ascii chars are allowed.
+    pub struct DispositionParsingUtf8 {
+        utf8 = true;
+        obsolete_syntax = false;
+        folding_ws = false;
+    }
+    fn can_be_quoted(bch: PartialCodePoint) -> bool {
+        let idx = bch.as_u8() as usize;
+        MediaTypeChars::check_at(idx, Any::new(Ws) | QText | DQuoteOrEscape)
+    }
+}
+
+/// runtime selection of one of the mime grammars
+///
+/// This is meant for cases where the grammar is only known at runtime, e.g. if it
+/// is configurable. If it is known at compiler time use the corresponding
+/// `ParsingImpl` directly.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum MimeGrammar {
+    /// the modern, us-ascii grammar (`MimeParsing`)
+    Modern,
+    /// the internationalized, modern grammar (`MimeParsingUtf8`)
+    ModernUtf8,
+    /// the obs grammar (`MimeObsParsing`)
+    Obs,
+    /// the internationalized obs grammar (`MimeObsParsingUtf8`)
+    ObsUtf8
+}
+
+impl MimeGrammar {
+    /// true for the obs grammars, which allow comments and FWS between parameters
+    pub fn is_obs(&self) -> bool {
+        match *self {
+            MimeGrammar::Obs | MimeGrammar::ObsUtf8 => true,
+            MimeGrammar::Modern | MimeGrammar::ModernUtf8 => false
+        }
+    }
+
+    /// calls `unquote` with the `ParsingImpl` corresponding to this grammar
+    pub fn unquote(&self, input: &str) -> Result<String, CoreError> {
+        use self::MimeGrammar::*;
+        match *self {
+            Modern => unquote::<MimeParsing>(input),
+            ModernUtf8 => unquote::<MimeParsingUtf8>(input),
+            Obs => unquote::<MimeObsParsing>(input),
+            ObsUtf8 => unquote::<MimeObsParsingUtf8>(input)
+        }
+    }
+
+    /// calls `unquote_with_offset` with the `ParsingImpl` corresponding to this grammar
+    pub fn unquote_with_offset(&self, input: &str) -> Result<String, OffsetError<CoreError>> {
+        use self::MimeGrammar::*;
+        match *self {
+            Modern => unquote_with_offset::<MimeParsing>(input),
+            ModernUtf8 => unquote_with_offset::<MimeParsingUtf8>(input),
+            Obs => unquote_with_offset::<MimeObsParsing>(input),
+            ObsUtf8 => unquote_with_offset::<MimeObsParsingUtf8>(input)
+        }
+    }
+
+    /// calls `unquote_cow` with the `ParsingImpl` corresponding to this grammar
+    pub fn unquote_cow<'a>(&self, input: &'a str) -> Result<Cow<'a, str>, CoreError> {
+        use self::MimeGrammar::*;
+        match *self {
+            Modern => unquote_cow::<MimeParsing>(input),
+            ModernUtf8 => unquote_cow::<MimeParsingUtf8>(input),
+            Obs => unquote_cow::<MimeObsParsing>(input),
+            ObsUtf8 => unquote_cow::<MimeObsParsingUtf8>(input)
+        }
+    }
+
+    /// calls `quote_if_needed` with the `QuotingClassifier` corresponding to this grammar
+    ///
+    /// Values which are (mime) tokens are returned bare.
+    pub fn quote_if_needed<'a>(&self, value: &'a str) -> Result<Cow<'a, str>, CoreError> {
+        use self::MimeGrammar::*;
+        match *self {
+            Modern => quote_if_needed::<MimeQuoting, MimeTokenValidator>(value),
+            ModernUtf8 => quote_if_needed::<MimeUtf8Quoting, MimeTokenValidator>(value),
+            Obs => quote_if_needed::<MimeObsQuoting, MimeTokenValidator>(value),
+            ObsUtf8 => quote_if_needed::<MimeObsUtf8Quoting, MimeTokenValidator>(value)
+        }
+    }
+}
+
+/// returns true if the value contains any non us-ascii char
+///
+/// Such values can only be represented with a internationalized grammar
+/// (e.g. `MimeGrammar::ModernUtf8`) or as RFC 2231 extended value.
+pub fn requires_utf8(value: &str) -> bool {
+    !value.is_ascii()
+}
+
+/// returns the modern grammar needed to represent the value
+///
+/// This is `MimeGrammar::ModernUtf8` if the value contains non us-ascii chars
+/// and `MimeGrammar::Modern` otherwise.
+pub fn recommended_grammar(value: &str) -> MimeGrammar {
+    if requires_utf8(value) {
+        MimeGrammar::ModernUtf8
+    } else {
+        MimeGrammar::Modern
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case)]
+
+    mod requires_utf8 {
+        use super::super::requires_utf8;
+
+        #[test]
+        fn us_ascii() {
+            assert!(!requires_utf8("plain value"));
+            assert!(!requires_utf8("\x7f"));
+            assert!(!requires_utf8(""));
+        }
+
+        #[test]
+        fn non_us_ascii() {
+            assert!(requires_utf8("café"));
+            assert!(requires_utf8("\u{80}"));
+        }
+    }
+
+    mod recommended_grammar {
+        use super::super::{recommended_grammar, MimeGrammar};
+
+        #[test]
+        fn us_ascii_uses_modern() {
+            assert_eq!(recommended_grammar("plain"), MimeGrammar::Modern);
+        }
+
+        #[test]
+        fn non_us_ascii_uses_modern_utf8() {
+            let grammar = recommended_grammar("café");
+            assert_eq!(grammar, MimeGrammar::ModernUtf8);
+            assert_eq!(grammar.quote_if_needed("café").unwrap(), "\"café\"");
+        }
+    }
+
+    mod MimeQuoting {
+        //it's not unused rustc just falls over wrt. the unused detection and `use super::*`
+        #[allow(unused_imports)]
+        use qs::spec::QuotingClassifier;
+        use super::super::{PartialCodePoint, MimeQuoting, QuotingClass};
+
+        mod classify_for_quoting {
+            use super::*;
+
+            #[test]
+            fn qtext_and_ws_are_qtext() {
+                for x in b"a~!\t " {
+                    let pcp = PartialCodePoint::from_utf8_byte(*x);
+                    let res = MimeQuoting::classify_for_quoting(pcp);
+                    assert_eq!(res, QuotingClass::QText);
+                }
+            }
+
+            #[test]
+            fn dquotes_and_escape_needs_quoting() {
+                for x in b"\"\\" {
+                    let pcp = PartialCodePoint::from_utf8_byte(*x);
+                    let res = MimeQuoting::classify_for_quoting(pcp);
+                    assert_eq!(res, QuotingClass::NeedsQuoting);
+                }
+            }
+
+            #[test]
+            fn ctls_and_non_us_ascii_are_invalid() {
+                for x in &[0x00u8, 0x7f, b'\r', b'\n', 0x80] {
+                    let pcp = PartialCodePoint::from_utf8_byte(*x);
+                    let res = MimeQuoting::classify_for_quoting(pcp);
+                    assert_eq!(res, QuotingClass::Invalid, "0x{:x}", x);
+                }
+            }
+        }
+    }
+
+    mod MimeStrictQuoting {
+        //it's not unused rustc just falls over wrt. the unused detection and `use super::*`
+        #[allow(unused_imports)]
+        use qs::spec::QuotingClassifier;
+        use super::super::{PartialCodePoint, MimeStrictQuoting, QuotingClass};
+
+        #[test]
+        fn nul_and_newline_are_invalid() {
+            for x in b"\x00\x0a" {
+                let pcp = PartialCodePoint::from_utf8_byte(*x);
+                let res = MimeStrictQuoting::classify_for_quoting(pcp);
+                assert_eq!(res, QuotingClass::Invalid, "0x{:x}", x);
+            }
+        }
+
+        #[test]
+        fn printable_symbol_needs_quoting() {
+            let pcp = PartialCodePoint::from_utf8_byte(b'"');
+            assert_eq!(MimeStrictQuoting::classify_for_quoting(pcp), QuotingClass::NeedsQuoting);
+        }
+
+        #[test]
+        fn htab_is_qtext() {
+            let pcp = PartialCodePoint::from_utf8_byte(b'\t');
+            assert_eq!(MimeStrictQuoting::classify_for_quoting(pcp), QuotingClass::QText);
+        }
+    }
+
+    mod MimeUtf8Quoting {
+        //it's not unused rustc just falls over wrt. the unused detection and `use super::*`
+        #[allow(unused_imports)]
+        use qs::spec::QuotingClassifier;
+        use super::super::{PartialCodePoint, MimeUtf8Quoting, QuotingClass};
+
+        mod classify_for_quoting {
+            use super::*;
+
+            #[test]
+            fn ctls_are_invalid() {
+                for x in 0x00u8..0x09 {
+                    let pcp = PartialCodePoint::from_utf8_byte(x);
+                    let res = MimeUtf8Quoting::classify_for_quoting(pcp);
+                    assert_eq!(res, QuotingClass::Invalid, "0x{:x}", x);
+                }
+                let pcp = PartialCodePoint::from_utf8_byte(0x7f);
+                assert_eq!(MimeUtf8Quoting::classify_for_quoting(pcp), QuotingClass::Invalid);
+            }
+
+            #[test]
+            fn tab_is_qtext() {
+                let pcp = PartialCodePoint::from_utf8_byte(b'\t');
+                assert_eq!(MimeUtf8Quoting::classify_for_quoting(pcp), QuotingClass::QText);
+            }
+
+            #[test]
+            fn non_us_ascii_is_qtext() {
+                for x in 0x80u8..0xff {
+                    let pcp = PartialCodePoint::from_utf8_byte(x);
+                    let res = MimeUtf8Quoting::classify_for_quoting(pcp);
+                    assert_eq!(res, QuotingClass::QText, "0x{:x}", x);
+                }
+                let pcp = PartialCodePoint::from_code_point('ä' as u32);
+                assert_eq!(MimeUtf8Quoting::classify_for_quoting(pcp), QuotingClass::QText);
+            }
+
+            #[test]
+            fn dquotes_and_escape_needs_quoting() {
+                for x in b"\"\\" {
+                    let pcp = PartialCodePoint::from_utf8_byte(*x);
+                    let res = MimeUtf8Quoting::classify_for_quoting(pcp);
+                    assert_eq!(res, QuotingClass::NeedsQuoting);
+                }
+            }
+        }
+    }
+
+    mod MimeQuoteWhitespace {
+        //it's not unused rustc just falls over wrt. the unused detection and `use super::*`
+        #[allow(unused_imports)]
+        use qs::spec::{QuotingClassifier, WithoutQuotingValidator};
+        use std::borrow::Cow;
+        use super::super::{PartialCodePoint, MimeQuoteWhitespace, MimeQuoting, QuotingClass};
+        use super::super::super::{classify, quote, quote_if_needed};
+
+        #[test]
+        fn ws_needs_quoting() {
+            for x in b" \t" {
+                let pcp = PartialCodePoint::from_utf8_byte(*x);
+                let res = MimeQuoteWhitespace::classify_for_quoting(pcp);
+                assert_eq!(res, QuotingClass::NeedsQuoting);
+                assert!(!MimeQuoteWhitespace::new().next(pcp));
+            }
+        }
+
+        #[test]
+        fn other_chars_like_mime_quoting() {
+            for x in 0u8..0x81 {
+                if x == b' ' || x == b'\t' {
+                    continue;
+                }
+                let pcp = PartialCodePoint::from_utf8_byte(x);
+                assert_eq!(
+                    MimeQuoteWhitespace::classify_for_quoting(pcp),
+                    MimeQuoting::classify_for_quoting(pcp),
+                    "0x{:x}", x
+                );
+            }
+        }
+
+        #[test]
+        fn values_with_spaces_are_flagged() {
+            assert_eq!(classify::<MimeQuoteWhitespace>("foo bar"), QuotingClass::NeedsQuoting);
+            assert_eq!(classify::<MimeQuoteWhitespace>("foobar"), QuotingClass::QText);
+            assert_eq!(classify::<MimeQuoting>("foo bar"), QuotingClass::QText);
+            assert_eq!(quote::<MimeQuoteWhitespace>("foo bar").unwrap(), "\"foo\\ bar\"");
+            assert_eq!(quote::<MimeQuoting>("foo bar").unwrap(), "foo bar");
+        }
+
+        fn quote_ws<'a>(value: &'a str) -> Cow<'a, str> {
+            quote_if_needed::<MimeQuoteWhitespace, MimeQuoteWhitespace>(value).unwrap()
+        }
+
+        #[test]
+        fn only_tokens_are_emitted_bare() {
+            assert!(matches!(quote_ws("foobar"), Cow::Borrowed("foobar")));
+            assert_eq!(quote_ws("foo bar"), "\"foo\\ bar\"");
+            assert_eq!(quote_ws("a\tb"), "\"a\\\tb\"");
+            assert_eq!(quote_ws("a;b"), "\"a;b\"");
+            assert_eq!(quote_ws("a=b"), "\"a=b\"");
+            assert_eq!(quote_ws("a/b"), "\"a/b\"");
+            assert_eq!(quote_ws("a(b"), "\"a(b\"");
+            assert_eq!(quote_ws(""), "\"\"");
+        }
+    }
+
+    mod MimeTokenValidator {
+        //it's a rust bug not detecting it's used when doing the unused checks
+        #[allow(unused_imports)]
+        use qs::spec::WithoutQuotingValidator;
+        use super::super::{MimeTokenValidator, PartialCodePoint};
+
+        fn validate(token: &str) -> bool {
+            let mut vali = MimeTokenValidator::new();
+            token.bytes().all(|bch| vali.next(PartialCodePoint::from_utf8_byte(bch))) && vali.end()
+        }
+
+        #[test]
+        fn empty_is_invalid() {
+            assert!(!validate(""));
+        }
+
+        #[test]
+        fn single_char_is_valid() {
+            assert!(validate("a"));
+        }
+
+        #[test]
+        fn normal_token_is_valid() {
+            assert!(validate("x-token.1"));
+        }
+
+        #[test]
+        fn non_token_chars_are_invalid() {
+            assert!(!validate("a b"));
+            assert!(!validate("a/b"));
+        }
+    }
+
+    mod MimeRestrictedNameValidator {
+        //it's a rust bug not detecting it's used when doing the unused checks
+        #[allow(unused_imports)]
+        use qs::spec::WithoutQuotingValidator;
+        use super::super::{MimeRestrictedNameValidator, MimeRestrictedTokenValidator, PartialCodePoint};
+
+        fn validate(name: &str) -> bool {
+            let mut vali = MimeRestrictedNameValidator::new();
+            name.bytes().all(|bch| vali.next(PartialCodePoint::from_utf8_byte(bch))) && vali.end()
+        }
+
+        #[test]
+        fn type_and_subtype_are_valid() {
+            assert!(validate("application"));
+            assert!(validate("json"));
+            assert!(validate("vnd.extra.yay+noo"));
+        }
+
+        #[test]
+        fn vendor_subtype_with_suffix_is_valid() {
+            assert!(validate("vnd.foo+json"));
+            assert!(!validate("vnd.foo+json;"));
+        }
+
+        #[test]
+        fn leading_dot_or_plus_is_invalid() {
+            assert!(!validate(".json"));
+            assert!(!validate("+json"));
+        }
+
+        #[test]
+        fn empty_is_invalid() {
+            assert!(!MimeRestrictedNameValidator::new().end());
+        }
+
+        #[test]
+        fn length_limit_is_checked() {
+            let name = "a".repeat(128);
+            assert!(!validate(&name));
+            assert!(validate(&name[..127]));
+        }
+
+        #[test]
+        fn failed_next_does_not_increase_counter() {
+            let mut vali = MimeRestrictedNameValidator::new();
+            assert!(!vali.next(PartialCodePoint::from_utf8_byte(b'.')));
+            assert_eq!(vali.count, 0);
+        }
+
+        #[test]
+        fn restricted_token_alias() {
+            let mut vali = MimeRestrictedTokenValidator::new();
+            assert!("vnd.foo+json".bytes().all(|bch| vali.next(PartialCodePoint::from_utf8_byte(bch))));
+            assert!(vali.end());
+        }
+    }
+
+    mod CharsetTokenValidator {
+        //it's a rust bug not detecting it's used when doing the unused checks
+        #[allow(unused_imports)]
+        use qs::spec::WithoutQuotingValidator;
+        use super::super::{CharsetTokenValidator, PartialCodePoint};
+
+        fn validate(charset: &str) -> bool {
+            let mut vali = CharsetTokenValidator::new();
+            charset.bytes().all(|bch| vali.next(PartialCodePoint::from_utf8_byte(bch))) && vali.end()
+        }
+
+        #[test]
+        fn common_charsets_are_valid() {
+            assert!(validate("utf-8"));
+            assert!(validate("UTF-8"));
+            assert!(validate("us-ascii"));
+            assert!(validate("ISO-8859-1"));
+        }
+
+        #[test]
+        fn empty_is_invalid() {
+            assert!(!validate(""));
+        }
+
+        #[test]
+        fn token_chars_above_z_are_invalid() {
+            assert!(!validate("utf{8}"));
+            assert!(!validate("utf~8"));
+        }
+    }
+
+    mod Rfc2045TokenValidator {
+        //it's a rust bug not detecting it's used when doing the unused checks
+        #[allow(unused_imports)]
+        use qs::spec::WithoutQuotingValidator;
+        use lut::Table;
+        use lookup_tables::{MediaTypeChars, Token};
+        use super::super::{Rfc2045TokenValidator, PartialCodePoint};
+
+        fn validate(token: &str) -> bool {
+            let mut vali = Rfc2045TokenValidator::new();
+            token.bytes().all(|bch| vali.next(PartialCodePoint::from_utf8_byte(bch))) && vali.end()
+        }
+
+        #[test]
+        fn tspecials_are_rejected() {
+            assert!(!validate("a@b"));
+            assert!(!validate("a/b"));
+            assert!(!validate("a b"));
+            assert!(!validate("a\x7fb"));
+        }
+
+        #[test]
+        fn token_chars_are_accepted() {
+            assert!(validate("a-b"));
+            assert!(validate("a.b"));
+            assert!(validate("x-Foo_1.2"));
+        }
+
+        #[test]
+        fn empty_is_invalid() {
+            assert!(!validate(""));
+        }
+
+        #[test]
+        fn accepts_exactly_token_chars() {
+            for bch in 0u8..0xff {
+                let mut vali = Rfc2045TokenValidator::new();
+                let expected = MediaTypeChars::check_at(bch as usize, Token);
+                assert_eq!(vali.next(PartialCodePoint::from_utf8_byte(bch)), expected, "0x{:x}", bch);
+            }
+        }
+    }
+
+    mod BoundaryValidator {
+        //it's a rust bug not detecting it's used when doing the unused checks
+        #[allow(unused_imports)]
+        use qs::spec::WithoutQuotingValidator;
+        use super::super::{BoundaryValidator, PartialCodePoint};
+
+        fn validate(boundary: &str) -> bool {
+            let mut vali = BoundaryValidator::new();
+            boundary.bytes().all(|bch| vali.next(PartialCodePoint::from_utf8_byte(bch))) && vali.end()
+        }
+
+        #[test]
+        fn valid_boundaries() {
+            assert!(validate("simple boundary"));
+            assert!(validate("----=_NextPart_000_0001"));
+            assert!(validate("'()+_,-./:=?"));
+            assert!(validate(&"a".repeat(70)));
+        }
+
+        #[test]
+        fn length_limits() {
+            assert!(!validate(""));
+            assert!(!validate(&"a".repeat(71)));
+        }
+
+        #[test]
+        fn trailing_space_is_invalid() {
+            assert!(!validate("boundary "));
+            assert!(validate(" boundary"));
+        }
+
+        #[test]
+        fn non_bchars_are_invalid() {
+            assert!(!validate("a\"b"));
+            assert!(!validate("a;b"));
+            assert!(!validate("a\tb"));
+        }
+    }
+
+    mod generate_boundary {
+        use super::super::super::is_valid_token;
+        use super::super::{generate_boundary, BoundaryValidator};
+
+        #[test]
+        fn is_deterministic() {
+            assert_eq!(generate_boundary(42), generate_boundary(42));
+            assert_ne!(generate_boundary(42), generate_boundary(43));
+        }
+
+        #[test]
+        fn is_valid() {
+            for seed in 0..100 {
+                let boundary = generate_boundary(seed);
+                assert!(is_valid_token::<BoundaryValidator>(&boundary), "{}", boundary);
+            }
+        }
+    }
+
+    mod DispositionParsing {
+        use qs::error::CoreError;
+        use super::super::super::unquote;
+        use super::super::{DispositionParsing, DispositionParsingUtf8, MimeParsing};
+
+        #[test]
+        fn quoted_filename() {
+            let res = unquote::<DispositionParsing>(r#""some \"file\".txt""#);
+            assert_eq!(res.unwrap(), r#"some "file".txt"#);
+            let res = unquote::<DispositionParsingUtf8>("\"ä.txt\"");
+            assert_eq!(res.unwrap(), "ä.txt");
+        }
+
+        #[test]
+        fn fws_is_rejected() {
+            let input = "\"some\r\n file.txt\"";
+            assert_eq!(unquote::<MimeParsing>(input).unwrap(), "some file.txt");
+            assert_eq!(unquote::<DispositionParsing>(input), Err(CoreError::InvalidChar));
+            assert_eq!(unquote::<DispositionParsingUtf8>(input), Err(CoreError::InvalidChar));
+        }
+
+        #[test]
+        fn utf8_only_in_utf8_variant() {
+            assert_eq!(unquote::<DispositionParsing>("\"ä\""), Err(CoreError::InvalidChar));
+        }
+    }
+
+    mod MimeGrammar {
+        use std::borrow::Cow;
+        use qs::error::CoreError;
+        use super::super::MimeGrammar;
+        use super::super::MimeGrammar::*;
+
+        const ALL: &[MimeGrammar] = &[Modern, ModernUtf8, Obs, ObsUtf8];
+
+        #[test]
+        fn common_input_is_accepted_by_all() {
+            let corpus = &[
+                ("token", "token"),
+                ("\"simple\"", "simple"),
+                (r#""a\"b\\c""#, r#"a"b\c"#),
+                ("\"a\r\n b\"", "a b"),
+            ];
+            for grammar in ALL {
+                for &(input, expected) in corpus {
+                    assert_eq!(grammar.unquote(input).unwrap(), expected, "{:?}", grammar);
+                }
+            }
+        }
+
+        #[test]
+        fn quote_if_needed() {
+            for grammar in ALL {
+                assert_eq!(grammar.quote_if_needed("utf-8").unwrap(), "utf-8");
+                assert_eq!(grammar.quote_if_needed("a b").unwrap(), "\"a b\"");
+                assert_eq!(grammar.quote_if_needed("").unwrap(), "\"\"");
+            }
+            assert_eq!(ModernUtf8.quote_if_needed("ä").unwrap(), "\"ä\"");
+            assert_eq!(Modern.quote_if_needed("ä"), Err(CoreError::InvalidChar));
+        }
+
+        #[test]
+        fn utf8_is_only_accepted_by_utf8_grammars() {
+            for grammar in ALL {
+                let res = grammar.unquote("\"ä\"");
+                match *grammar {
+                    ModernUtf8 | ObsUtf8 => assert_eq!(res.unwrap(), "ä"),
+                    Modern | Obs => assert_eq!(res, Err(CoreError::InvalidChar))
+                }
+            }
+        }
+
+        #[test]
+        fn quoted_ctls_are_only_accepted_by_obs_grammars() {
+            for grammar in ALL {
+                let res = grammar.unquote("\"a\\\x01\"");
+                match *grammar {
+                    Obs | ObsUtf8 => assert_eq!(res.unwrap(), "a\x01"),
+                    Modern | ModernUtf8 => assert_eq!(res, Err(CoreError::UnquoteableCharQuoted))
+                }
+            }
+        }
+
+        #[test]
+        fn unquote_cow_borrows_if_possible() {
+            for grammar in ALL {
+                let res = grammar.unquote_cow("\"simple\"").unwrap();
+                assert!(matches!(res, Cow::Borrowed("simple")));
+                match grammar.unquote_cow(r#""a\"b""#).unwrap() {
+                    Cow::Owned(value) => assert_eq!(value, r#"a"b"#),
+                    Cow::Borrowed(_) => panic!("expected a allocated value")
+                }
+            }
+        }
+    }
+}
@@ -5,11 +5,13 @@ use lookup_tables::{
     ObsQTextWs, QTextWs,
     Ws
 };
-use qs::error::CoreError;
+pub use qs::error::CoreError;
+pub use qs::spec::QuotingClass;
 use qs::spec::{
     PartialCodePoint,
     ParsingImpl,
-    State
+    State,
+    WithoutQuotingValidator
 };
 
 mod other;
@@ -18,6 +20,43 @@ mod http;
 pub use self::http::*;
 mod mime;
 pub use self::mime::*;
+mod encode;
+pub use self::encode::*;
+mod decode;
+pub use self::decode::*;
+mod stream;
+pub use self::stream::*;
+
+/// returns true if a new `WithoutQuotingValidator` accepts the whole input
+///
+/// Every char is passed to the validator (as `PartialCodePoint`) and
+/// then `end` is called, so whether or not a empty input is valid
+/// depends on the validator.
+pub fn is_valid_token<V: WithoutQuotingValidator + Default>(input: &str) -> bool {
+    let mut validator = V::default();
+    input.chars().all(|ch| validator_next(&mut validator, ch))
+        && validator.end()
+}
+
+/// passes a char to the validator, returns false if the validator doesn't accept it
+///
+/// This converts the char to a `PartialCodePoint`, so validators can be driven
+/// char by char without having to use the `quoted-string` crate directly.
+pub fn validator_next<V: WithoutQuotingValidator>(validator: &mut V, ch: char) -> bool {
+    validator.next(PartialCodePoint::from_code_point(ch as u32))
+}
+
+/// creates a `PartialCodePoint` from a byte which is not known to be part of utf-8
+///
+/// Any byte > 0x7f is mapped to 0xFF, like `PartialCodePoint::from_code_point` does
+/// for non us-ascii code points.
+fn pcp_from_byte(bch: u8) -> PartialCodePoint {
+    if bch > 0x7f {
+        PartialCodePoint::from_code_point(bch as u32)
+    } else {
+        PartialCodePoint::from_utf8_byte(bch)
+    }
+}
 
 /// This is an extension trait for implementing MediaType parsing in context of Mime
 pub trait MimeParsingExt: ParsingImpl {
@@ -25,6 +64,10 @@ pub trait MimeParsingExt: ParsingImpl {
     const ALLOW_UTF8: bool;
     /// is true if the `obs-` part of the grammar is supported
     const OBS: bool;
+    /// is true if FWS (i.e. `"\r\n"` followed by `' '` or `'\t'`) are allowed
+    ///
+    /// If this is false any `'\r'` will be treated as invalid char.
+    const ALLOW_FWS: bool = true;
 
     /// crate the custom state based on the `FWSState` state and `emit`
     ///
@@ -45,7 +88,7 @@ pub trait MimeParsingExt: ParsingImpl {
     /// 1. return `Ok((State::Normal, true))` if it is qtext in context of
     ///    `Self::ALLOW_UTF8` and `Self::OBS`
     /// 2. return `Ok(Self::custom_state(FWSState::HitCr, false))` if the input
-    ///    was `'\r'`
+    ///    was `'\r'` and `Self::ALLOW_FWS` is true
     /// 3. else return `Err(CoreError::InvalidChar)`
     ///
     /// Note if `Self::ALLOW_UTF8` is set to true any `bch.as_u8() > 0x7f` will be treated
@@ -63,7 +106,7 @@ pub trait MimeParsingExt: ParsingImpl {
 
         if is_qtext_ws || (Self::ALLOW_UTF8 && iu8 > 0x7f) {
             Ok((State::Normal, true))
-        } else if iu8 == b'\r' {
+        } else if Self::ALLOW_FWS && iu8 == b'\r' {
             Ok(Self::custom_state(FWSState::HitCr, false))
         } else {
             Err(CoreError::InvalidChar)
@@ -85,38 +128,89 @@ pub enum FWSState {
     HadFws
 }
 
+impl Default for FWSState {
+    fn default() -> Self {
+        FWSState::new()
+    }
+}
+
 impl FWSState {
 
+    /// the state after the `'\r'` starting a FWS
+    pub fn new() -> Self {
+        FWSState::HitCr
+    }
+
+    /// true if the FWS is complete, i.e. `"\r\n"` was followed by at last one ws char
+    ///
+    /// Only in the non obs grammar the state stays `HadFws` after the ws char, in the
+    /// obs grammar `advance_fold` returns `None` instead.
+    pub fn is_complete(&self) -> bool {
+        *self == FWSState::HadFws
+    }
+
+    /// advances the state without a `MimeParsingExt` implementation
+    ///
+    /// This only handles the folding itself: It assures that `'\r'` is followed by
+    /// `'\n'` which in turn is followed by `' '` or `'\t'`. The returned bool is
+    /// true if the char is part of the value (i.e. the ws char after the `"\r\n"`).
+    ///
+    /// `None` is returned if the fold ended, for the obs grammar this is directly
+    /// after the ws char following the `"\r\n"`. For the non obs grammar it's on the
+    /// first non ws char after the FWS, which is *not* consumed (the returned bool is
+    /// false) and has to be handled by the caller.
+    ///
+    /// # Error
+    ///
+    /// `CoreError::InvalidChar` if a `'\r'` is not followed by `'\n'` or a `"\r\n"`
+    /// is not followed by ws
+    pub fn advance_fold(self, bch: PartialCodePoint, obs: bool)
+        -> Result<(Option<FWSState>, bool), CoreError>
+    {
+        use self::FWSState::*;
+        let iu8 = bch.as_u8();
+        match self {
+            HitCr if iu8 == b'\n' => Ok((Some(HitNl), false)),
+            HitNl if iu8 == b' ' || iu8 == b'\t' => {
+                if obs {
+                    Ok((None, true))
+                } else {
+                    Ok((Some(HadFws), true))
+                }
+            },
+            HitCr | HitNl => Err(CoreError::InvalidChar),
+            HadFws if iu8 == b' ' || iu8 == b'\t' => Ok((Some(HadFws), true)),
+            HadFws => Ok((None, false))
+        }
+    }
+
     /// default implementation for handing FWSState state transitions
     ///
     /// Handles the state transition wrt. a given MimeParsingExt implementation.
     /// It assures that after a `\r` only `\n` can follow and after a `\n` either
     /// `' '` or `'\t'` has to follow. Lastly it makes sure that between two
     /// FWS there has to be at last one non ws character (at last in the non obs grammar).
+    /// The folding itself is handled by `advance_fold`, only the `HadFws` state
+    /// depends on the grammar.
+    ///
+    /// # Error
     ///
+    /// `CoreError::InvalidChar` if a `'\r'` is not followed by `'\n'` or a `"\r\n"`
+    /// is not followed by ws. A `'\n'` not preceded by `'\r'` never reaches this
+    /// state machine, it's already rejected as invalid char by `handle_normal_state`.
+    /// `CoreError` is defined by the `quoted-string` crate, so there is no more
+    /// specific error for this.
     pub fn advance<Impl: MimeParsingExt>(self, bch: PartialCodePoint)
                                          -> Result<(State<Impl>, bool), CoreError>
     {
         use self::FWSState::*;
         let iu8 = bch.as_u8();
         match self {
-            HitCr => {
-                if iu8 == b'\n' {
-                    Ok(Impl::custom_state(FWSState::HitNl, false))
-                } else {
-                    Err(CoreError::InvalidChar)
-                }
-            },
-            HitNl => {
-                if iu8 == b' ' || iu8 == b'\t' {
-                    if Impl::OBS {
-                        Ok((State::Normal, true))
-                    } else {
-                        //the new grammar does not allow ws-only lines, `obs-` one does
-                        Ok(Impl::custom_state(FWSState::HadFws, true))
-                    }
-                } else {
-                    Err(CoreError::InvalidChar)
+            HitCr | HitNl => {
+                //the new grammar does not allow ws-only lines, `obs-` one does
+                match self.advance_fold(bch, Impl::OBS)? {
+                    (Some(state), emit) => Ok(Impl::custom_state(state, emit)),
+                    (None, emit) => Ok((State::Normal, emit))
                 }
             },
             HadFws => {
@@ -147,4 +241,129 @@ impl FWSState {
     }
 }
 
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case)]
+
+    mod plain_api {
+        // only uses std and this crate, no `quoted-string` types have to be constructed
+        use chars::is_token_char;
+        use quoted_string::{
+            classify, classify_char, quote, unquote, is_valid_token, validator_next,
+            MimeQuoting, MimeParsing, MimeTokenValidator, QuotingClass, CoreError
+        };
+
+        #[test]
+        fn token_chars() {
+            assert!(is_token_char(b'a'));
+            assert!(!is_token_char(b' '));
+            assert!(is_valid_token::<MimeTokenValidator>("abc"));
+        }
+
+        #[test]
+        fn classifying() {
+            assert_eq!(classify::<MimeQuoting>("abc"), QuotingClass::QText);
+            assert_eq!(classify::<MimeQuoting>("a\\b"), QuotingClass::NeedsQuoting);
+            assert_eq!(classify_char::<MimeQuoting>('a'), QuotingClass::QText);
+            assert_eq!(classify_char::<MimeQuoting>('"'), QuotingClass::NeedsQuoting);
+            assert_eq!(classify_char::<MimeQuoting>('\0'), QuotingClass::Invalid);
+        }
+
+        #[test]
+        fn quoting_and_unquoting() {
+            let quoted = quote::<MimeQuoting>("a\"b").unwrap();
+            assert_eq!(quoted, r#""a\"b""#);
+            assert_eq!(unquote::<MimeParsing>(&quoted).unwrap(), "a\"b");
+            assert_eq!(unquote::<MimeParsing>("\"a"), Err(CoreError::DoesNotEndWithDQuotes));
+        }
 
+        #[test]
+        fn driving_a_validator() {
+            let mut validator = MimeTokenValidator::default();
+            assert!(validator_next(&mut validator, 'a'));
+            assert!(!validator_next(&mut validator, '('));
+        }
+    }
+
+    mod is_valid_token {
+        use super::super::{is_valid_token, MimeTokenValidator, HttpTokenValidator};
+
+        #[test]
+        fn token() {
+            assert!(is_valid_token::<MimeTokenValidator>("abc"));
+            assert!(is_valid_token::<HttpTokenValidator>("abc"));
+        }
+
+        #[test]
+        fn empty() {
+            assert!(!is_valid_token::<MimeTokenValidator>(""));
+        }
+
+        #[test]
+        fn non_token() {
+            assert!(!is_valid_token::<MimeTokenValidator>("a b"));
+            assert!(!is_valid_token::<MimeTokenValidator>("ä"));
+        }
+    }
+
+    mod FWSState {
+        use qs::error::CoreError;
+        use qs::spec::PartialCodePoint;
+        use super::super::FWSState;
+
+        fn run(input: &[u8], obs: bool) -> Result<(Option<FWSState>, usize), CoreError> {
+            // the input starts with the '\r'
+            let mut state = FWSState::new();
+            let mut emitted = 0;
+            for bch in &input[1..] {
+                let (next, emit) = state.advance_fold(PartialCodePoint::from_utf8_byte(*bch), obs)?;
+                if emit {
+                    emitted += 1;
+                }
+                match next {
+                    Some(next) => state = next,
+                    None => return Ok((None, emitted))
+                }
+            }
+            Ok((Some(state), emitted))
+        }
+
+        #[test]
+        fn valid_fold() {
+            assert_eq!(run(b"\r\n ", true), Ok((None, 1)));
+            let (state, emitted) = run(b"\r\n \t", false).unwrap();
+            assert!(state.unwrap().is_complete());
+            assert_eq!(emitted, 2);
+            assert_eq!(run(b"\r\n a", false), Ok((None, 1)));
+        }
+
+        #[test]
+        fn lone_cr() {
+            let (state, _) = run(b"\r", true).unwrap();
+            assert!(!state.unwrap().is_complete());
+            assert_eq!(run(b"\ra", true), Err(CoreError::InvalidChar));
+            assert_eq!(run(b"\r\r", false), Err(CoreError::InvalidChar));
+        }
+
+        #[test]
+        fn lone_cr_and_lf_in_quoted_string() {
+            use quoted_string::MimeGrammar::*;
+            for grammar in &[Modern, ModernUtf8, Obs, ObsUtf8] {
+                assert_eq!(grammar.unquote("\"a\rb\""), Err(CoreError::InvalidChar), "{:?}", grammar);
+                assert_eq!(grammar.unquote("\"a\nb\""), Err(CoreError::InvalidChar), "{:?}", grammar);
+                assert_eq!(grammar.unquote("\"a\n b\""), Err(CoreError::InvalidChar), "{:?}", grammar);
+                assert_eq!(grammar.unquote("\"a\r\n\n b\""), Err(CoreError::InvalidChar), "{:?}", grammar);
+                assert_eq!(grammar.unquote("\"a\r\n \nb\""), Err(CoreError::InvalidChar), "{:?}", grammar);
+                assert_eq!(grammar.unquote("\"a\r\n b\"").unwrap(), "a b", "{:?}", grammar);
+            }
+        }
+
+        #[test]
+        fn crlf_not_followed_by_ws() {
+            assert_eq!(run(b"\r\na", true), Err(CoreError::InvalidChar));
+            assert_eq!(run(b"\r\n\r", false), Err(CoreError::InvalidChar));
+            let (state, _) = run(b"\r\n", true).unwrap();
+            assert!(!state.unwrap().is_complete());
+        }
+    }
+}
@@ -180,6 +180,71 @@ impl WithoutQuotingValidator for StrictTokenValidator {
     }
 }
 
+/// a `WithoutQuotingValidator` which only limits the length (in code points)
+///
+/// Any char is accepted as long as there were less than `max` chars before,
+/// so it's mainly useful combined with other validators using `And`, e.g. to
+/// apply the 127 char limit of RFC 6838.
+///
+/// As the limit is a runtime value this type does not implement `Default`, so
+/// it (and any `And` containing it) can not be used with functions creating
+/// the validator themselves (like `is_valid_token` or `quote_if_needed`). Instead
+/// either wrap it in a type with a fixed limit which implements `Default`, or drive
+/// a explicitly created value, e.g. `And(MimeTokenValidator::new(), MaxLenValidator::new(127))`,
+/// using `validator_next`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct MaxLenValidator {
+    max: usize,
+    count: usize
+}
+
+impl MaxLenValidator {
+    /// creates a new validator accepting at most `max` chars
+    pub fn new(max: usize) -> Self {
+        MaxLenValidator { max, count: 0 }
+    }
+}
+
+impl WithoutQuotingValidator for MaxLenValidator {
+    fn next(&mut self, _pcp: PartialCodePoint) -> bool {
+        if self.count < self.max {
+            self.count += 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// a `WithoutQuotingValidator` combinator accepting the input only if both validators accept it
+///
+/// `next` returns true if both validators accept the code point and `end` returns true
+/// if both validators are satisfied. As required by `WithoutQuotingValidator` the state
+/// is unchanged if `next` returns false, to achieve this the first validator is restored
+/// if it accepted the code point but the second didn't.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Default)]
+pub struct And<A, B>(pub A, pub B);
+
+impl<A, B> WithoutQuotingValidator for And<A, B>
+    where A: WithoutQuotingValidator + Clone, B: WithoutQuotingValidator
+{
+    fn next(&mut self, pcp: PartialCodePoint) -> bool {
+        let backup = self.0.clone();
+        if !self.0.next(pcp) {
+            return false;
+        }
+        if !self.1.next(pcp) {
+            self.0 = backup;
+            return false;
+        }
+        true
+    }
+
+    fn end(&self) -> bool {
+        self.0.end() && self.1.end()
+    }
+}
+
 #[cfg(test)]
 mod test {
     #![allow(non_snake_case)]
@@ -199,7 +264,7 @@ mod test {
                     let pcp = PartialCodePoint::from_utf8_byte(x);
                     let can_be_quoted = NormalParsingImpl::can_be_quoted(pcp);
                     match x {
-                        b'\t' | b' '...b'~' => assert!(can_be_quoted),
+                        b'\t' | b' '..=b'~' => assert!(can_be_quoted),
                         _ => assert!(!can_be_quoted)
                     }
                 }
@@ -226,7 +291,7 @@ mod test {
 
             #[test]
             fn ws_is_valid() {
-                for x in &[b'\t', b' '] {
+                for x in b"\t " {
                     let pcp = PartialCodePoint::from_utf8_byte(*x);
                     let res = NormalParsingImpl::handle_normal_state(pcp);
                     assert_eq!(res, Ok((State::Normal, true)))
@@ -256,7 +321,7 @@ mod test {
 
             #[test]
             fn only_dquotes_and_escape_can_be_quoted() {
-                for x in &[b'"',b'\\'] {
+                for x in b"\"\\" {
                     let pcp = PartialCodePoint::from_utf8_byte(*x);
                     let can_be_quoted = StrictParsingImpl::can_be_quoted(pcp);
                     match *x {
@@ -287,7 +352,7 @@ mod test {
 
             #[test]
             fn ws_is_valid() {
-                for x in &[b'\t', b' '] {
+                for x in b"\t " {
                     let pcp = PartialCodePoint::from_utf8_byte(*x);
                     let res = StrictParsingImpl::handle_normal_state(pcp);
                     assert_eq!(res, Ok((State::Normal, true)))
@@ -317,7 +382,7 @@ mod test {
 
             #[test]
             fn dquotes_and_escape_needs_quoting() {
-                for x in &[b'"', b'\\'] {
+                for x in b"\"\\" {
                     let pcp = PartialCodePoint::from_utf8_byte(*x);
                     let res = AnyQuoting::classify_for_quoting(pcp);
                     assert_eq!(res, QuotingClass::NeedsQuoting);
@@ -348,7 +413,7 @@ mod test {
 
             #[test]
             fn dquotes_and_escape_needs_quoting() {
-                for x in &[b'"', b'\\'] {
+                for x in b"\"\\" {
                     let pcp = PartialCodePoint::from_utf8_byte(*x);
                     let res = NormalQuoting::classify_for_quoting(pcp);
                     assert_eq!(res, QuotingClass::NeedsQuoting);
@@ -367,7 +432,7 @@ mod test {
 
             #[test]
             fn ws_is_qtext() {
-                for x in &[ b' ', b'\t'] {
+                for x in b" \t" {
                     let pcp = PartialCodePoint::from_utf8_byte(*x);
                     let res = NormalQuoting::classify_for_quoting(pcp);
                     assert_eq!(res, QuotingClass::QText);
@@ -462,4 +527,102 @@ mod test {
 
         }
     }
-}
\ No newline at end of file
+
+    mod MaxLenValidator {
+        use qs::spec::{PartialCodePoint, WithoutQuotingValidator};
+        use super::super::MaxLenValidator;
+
+        fn feed(vali: &mut MaxLenValidator, count: usize) -> bool {
+            (0..count).all(|_| vali.next(PartialCodePoint::from_code_point('a' as u32)))
+        }
+
+        #[test]
+        fn exactly_max() {
+            let mut vali = MaxLenValidator::new(3);
+            assert!(feed(&mut vali, 3));
+            assert!(vali.end());
+        }
+
+        #[test]
+        fn max_plus_one() {
+            let mut vali = MaxLenValidator::new(3);
+            assert!(feed(&mut vali, 3));
+            assert!(!vali.next(PartialCodePoint::from_code_point('a' as u32)));
+            // the rejected char is not counted
+            assert!(vali.end());
+        }
+
+        #[test]
+        fn any_char_is_accepted() {
+            let mut vali = MaxLenValidator::new(3);
+            assert!(vali.next(PartialCodePoint::from_code_point(' ' as u32)));
+            assert!(vali.next(PartialCodePoint::from_code_point('\0' as u32)));
+            assert!(vali.next(PartialCodePoint::from_code_point('ä' as u32)));
+            assert!(vali.end());
+        }
+    }
+
+    mod And {
+        use qs::spec::{PartialCodePoint, WithoutQuotingValidator};
+        use super::super::super::{is_valid_token, validator_next, MimeTokenValidator};
+        use super::super::{And, MaxLenValidator};
+
+        /// accepts at most 3 chars
+        #[derive(Copy, Clone, Debug)]
+        struct MaxLen3(MaxLenValidator);
+
+        impl Default for MaxLen3 {
+            fn default() -> Self {
+                MaxLen3(MaxLenValidator::new(3))
+            }
+        }
+
+        impl WithoutQuotingValidator for MaxLen3 {
+            fn next(&mut self, pcp: PartialCodePoint) -> bool {
+                self.0.next(pcp)
+            }
+
+            fn end(&self) -> bool {
+                self.0.end()
+            }
+        }
+
+        type ShortToken = And<MimeTokenValidator, MaxLen3>;
+
+        #[test]
+        fn accepts_if_both_accept() {
+            assert!(is_valid_token::<ShortToken>("abc"));
+        }
+
+        #[test]
+        fn explicit_value_driven_with_validator_next() {
+            let mut vali = And(MimeTokenValidator::new(), MaxLenValidator::new(2));
+            assert!("ab".chars().all(|ch| validator_next(&mut vali, ch)));
+            assert!(!validator_next(&mut vali, 'c'));
+            assert!(vali.end());
+        }
+
+        #[test]
+        fn rejects_if_first_rejects() {
+            assert!(!is_valid_token::<ShortToken>("a b"));
+            assert!(!is_valid_token::<ShortToken>(""));
+        }
+
+        #[test]
+        fn rejects_if_second_rejects() {
+            assert!(!is_valid_token::<ShortToken>("abcd"));
+        }
+
+        #[test]
+        fn state_is_unchanged_if_rejected() {
+            let mut vali = ShortToken::default();
+            for _ in 0..3 {
+                assert!(vali.next(PartialCodePoint::from_code_point('a' as u32)));
+            }
+            let before = vali.0;
+            assert!(!vali.next(PartialCodePoint::from_code_point('a' as u32)));
+            assert_eq!(vali.0, before);
+            assert!(vali.end());
+        }
+    }
+}
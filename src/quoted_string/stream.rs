@@ -0,0 +1,142 @@
+use qs::error::CoreError;
+use qs::spec::{
+    PartialCodePoint,
+    ParsingImpl,
+    ScanAutomaton,
+};
+
+/// a incremental parser for a quoted-string which is received in chunks
+///
+/// This validates a quoted-string wrt. the given `ParsingImpl` without needing
+/// the whole input at once, e.g. when header bytes arrive in arbitrary fragments
+/// from the network. The result is the same independent of how the input is split
+/// into chunks.
+#[derive(Debug, Clone)]
+pub struct StreamingQuotedParser<P: ParsingImpl> {
+    automaton: ScanAutomaton<P>,
+    failed: bool
+}
+
+impl<P: ParsingImpl> Default for StreamingQuotedParser<P> {
+    fn default() -> Self {
+        StreamingQuotedParser::new()
+    }
+}
+
+impl<P: ParsingImpl> StreamingQuotedParser<P> {
+
+    /// create a new parser expecting the opening `'"'` as first byte
+    pub fn new() -> Self {
+        StreamingQuotedParser { automaton: ScanAutomaton::new(), failed: false }
+    }
+
+    /// true if the closing `'"'` was found
+    pub fn is_done(&self) -> bool {
+        self.automaton.did_end()
+    }
+
+    /// feeds the next chunk of input to the parser
+    ///
+    /// Returns the number of bytes consumed, which is less than `chunk.len()`
+    /// if the quoted-string ended in the chunk (the closing `'"'` is counted as
+    /// consumed). Once the quoted-string ended any further chunk consumes 0 bytes.
+    ///
+    /// # Error
+    ///
+    /// any error the `ParsingImpl` returns, after a error any further call to
+    /// `push` returns `CoreError::AdvancedFailedAutomaton`
+    pub fn push(&mut self, chunk: &[u8]) -> Result<usize, CoreError> {
+        if self.failed {
+            return Err(CoreError::AdvancedFailedAutomaton);
+        }
+        if self.is_done() {
+            return Ok(0);
+        }
+        for (idx, bch) in chunk.iter().enumerate() {
+            if let Err(err) = self.automaton.advance(PartialCodePoint::from_utf8_byte(*bch)) {
+                self.failed = true;
+                return Err(err);
+            }
+            if self.is_done() {
+                return Ok(idx + 1);
+            }
+        }
+        Ok(chunk.len())
+    }
+
+    /// ends parsing
+    ///
+    /// # Error
+    ///
+    /// - `CoreError::DoesNotEndWithDQuotes` if the quoted-string was not closed
+    /// - `CoreError::AdvancedFailedAutomaton` if `push` failed before
+    pub fn finish(mut self) -> Result<(), CoreError> {
+        if self.failed {
+            return Err(CoreError::AdvancedFailedAutomaton);
+        }
+        self.automaton.end()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case)]
+
+    mod StreamingQuotedParser {
+        use qs::error::CoreError;
+        use super::super::super::{MimeParsing, MimeObsParsingUtf8, quoted_string_end};
+        use super::super::StreamingQuotedParser;
+
+        fn feed<P: ::qs::spec::ParsingImpl>(chunks: &[&[u8]]) -> Result<usize, CoreError> {
+            let mut parser = StreamingQuotedParser::<P>::new();
+            let mut consumed = 0;
+            for chunk in chunks {
+                consumed += parser.push(chunk)?;
+            }
+            parser.finish()?;
+            Ok(consumed)
+        }
+
+        #[test]
+        fn identical_result_for_every_split() {
+            let inputs = [
+                "\"abc\"", "\"a\\\"b\"", "\"a\r\n b\" trailing", "\"ä\\ö\"", "\"abc", "\"a\0b\"", "abc"
+            ];
+            for input in inputs.iter() {
+                let bytes = input.as_bytes();
+                let whole = feed::<MimeObsParsingUtf8>(&[bytes]);
+                if input.starts_with('"') {
+                    let expected = quoted_string_end::<MimeObsParsingUtf8>(input);
+                    assert_eq!(whole, expected, "{:?}", input);
+                }
+                for split in 0..bytes.len() + 1 {
+                    let (left, right) = bytes.split_at(split);
+                    assert_eq!(feed::<MimeObsParsingUtf8>(&[left, right]), whole, "{:?} at {}", input, split);
+                }
+                let single_bytes = bytes.chunks(1).collect::<Vec<_>>();
+                assert_eq!(feed::<MimeObsParsingUtf8>(&single_bytes), whole, "{:?}", input);
+            }
+        }
+
+        #[test]
+        fn consumed_bytes_stop_at_closing_dquote() {
+            let mut parser = StreamingQuotedParser::<MimeParsing>::new();
+            assert_eq!(parser.push(b"\"ab"), Ok(3));
+            assert!(!parser.is_done());
+            assert_eq!(parser.push(b"c\"; x=y"), Ok(2));
+            assert!(parser.is_done());
+            assert_eq!(parser.push(b"more"), Ok(0));
+            assert_eq!(parser.finish(), Ok(()));
+        }
+
+        #[test]
+        fn errors() {
+            let mut parser = StreamingQuotedParser::<MimeParsing>::new();
+            assert_eq!(parser.push(b"\"a"), Ok(2));
+            assert_eq!(parser.clone().finish(), Err(CoreError::DoesNotEndWithDQuotes));
+            assert_eq!(parser.push(b"\0"), Err(CoreError::InvalidChar));
+            assert_eq!(parser.push(b"\""), Err(CoreError::AdvancedFailedAutomaton));
+            assert_eq!(parser.finish(), Err(CoreError::AdvancedFailedAutomaton));
+        }
+    }
+}